@@ -1,6 +1,7 @@
 //! Voxel bounds
 
 use cgmath::{Point3, Vector3};
+use collision::Ray3;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(missing_docs)]
@@ -88,4 +89,35 @@ impl T {
     (other.z >> lg_ratio) == self.z &&
     true
   }
+
+  /// If a ray passes through this voxel, return the time at which it enters the voxel.
+  /// Rays starting inside the voxel enter it at time 0.
+  pub fn ray_entry(&self, ray: &Ray3<f32>) -> Option<f32> {
+    let (low, high) = self.corners();
+    ray_entry(&low, &high, ray)
+  }
+}
+
+/// If a ray passes through the box from `low` to `high`, return the time at which it enters it.
+/// Rays starting inside the box enter it at time 0.
+pub fn ray_entry(low: &Point3<f32>, high: &Point3<f32>, ray: &Ray3<f32>) -> Option<f32> {
+  let mut enter = 0.0_f32;
+  let mut exit = f32::INFINITY;
+  for d in 0..3 {
+    if ray.direction[d] == 0.0 {
+      if ray.origin[d] < low[d] || ray.origin[d] >= high[d] {
+        return None
+      }
+    } else {
+      let t0 = (low[d] - ray.origin[d]) / ray.direction[d];
+      let t1 = (high[d] - ray.origin[d]) / ray.direction[d];
+      enter = enter.max(t0.min(t1));
+      exit = exit.min(t0.max(t1));
+    }
+  }
+  if enter <= exit {
+    Some(enter)
+  } else {
+    None
+  }
 }
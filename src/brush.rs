@@ -1,6 +1,9 @@
 //! Voxel brush module
 
-use collision::Aabb3;
+use cgmath::{Point3, InnerSpace};
+use collision::{Aabb, Aabb3};
+use std::fmt;
+use std::sync::Arc;
 
 #[allow(missing_docs)]
 pub type Bounds = Aabb3<i32>;
@@ -14,6 +17,127 @@ pub struct T<Mosaic> {
   pub mosaic: Mosaic,
   /// lg of the smallest voxel size this brush will touch.
   pub min_lg_size: i16,
+  /// How strongly this brush is applied, in [0, 1]. Voxels that can't be blended, like whole
+  /// volumes of one material, only change where the weight is at least 0.5.
+  pub strength: f32,
+  /// How this brush's strength falls off away from a centre, if it does.
+  pub falloff: Option<Falloff>,
 }
 
-unsafe impl<Mosaic> Send for T<Mosaic> {}
+/// A brush with full strength everywhere.
+pub fn new<Mosaic>(bounds: Bounds, mosaic: Mosaic, min_lg_size: i16) -> T<Mosaic> {
+  T {
+    bounds: bounds,
+    mosaic: mosaic,
+    min_lg_size: min_lg_size,
+    strength: 1.0,
+    falloff: None,
+  }
+}
+
+/// The shape of a falloff, taking the distance from its centre (as a fraction of its radius)
+/// to a weight in [0, 1].
+#[derive(Clone)]
+pub enum Profile {
+  /// Weight falls linearly from 1 at the centre to 0 at the radius.
+  Linear,
+  /// Like `Linear`, but smoothed at the centre and the radius.
+  Smoothstep,
+  #[allow(missing_docs)]
+  Custom(Arc<dyn Fn(f32) -> f32 + Send + Sync>),
+}
+
+impl fmt::Debug for Profile {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Profile::Linear => write!(f, "Linear"),
+      Profile::Smoothstep => write!(f, "Smoothstep"),
+      Profile::Custom(_) => write!(f, "Custom(..)"),
+    }
+  }
+}
+
+impl Profile {
+  /// The weight at a distance `t` from the centre.
+  pub fn weight(&self, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    let weight =
+      match *self {
+        Profile::Linear => 1.0 - t,
+        Profile::Smoothstep => 1.0 - t*t*(3.0 - 2.0*t),
+        Profile::Custom(ref f) => f(t),
+      };
+    weight.clamp(0.0, 1.0)
+  }
+}
+
+/// A brush's strength falling off with distance from a point.
+#[derive(Debug, Clone)]
+#[allow(missing_docs)]
+pub struct Falloff {
+  pub center: Point3<f32>,
+  pub radius: f32,
+  pub profile: Profile,
+}
+
+impl Falloff {
+  /// Fall off from the centre of `bounds` to its nearest face.
+  pub fn within(bounds: &Bounds, profile: Profile) -> Falloff {
+    let min = bounds.min().cast::<f32>();
+    let max = bounds.max().cast::<f32>();
+    let half = (max - min) / 2.0;
+    Falloff {
+      center: min + half,
+      radius: half.x.min(half.y).min(half.z),
+      profile: profile,
+    }
+  }
+
+  /// The weight at a point.
+  pub fn weight(&self, p: &Point3<f32>) -> f32 {
+    let distance = (*p - self.center).magnitude();
+    if self.radius > 0.0 {
+      self.profile.weight(distance / self.radius)
+    } else {
+      0.0
+    }
+  }
+}
+
+impl<Mosaic> T<Mosaic> {
+  /// Whether this brush applies with full weight everywhere.
+  pub fn is_hard(&self) -> bool {
+    self.strength >= 1.0 && self.falloff.is_none()
+  }
+
+  /// How strongly this brush applies at a point, in [0, 1].
+  pub fn weight(&self, p: &Point3<f32>) -> f32 {
+    let strength = self.strength.clamp(0.0, 1.0);
+    match self.falloff {
+      None => strength,
+      Some(ref falloff) => strength * falloff.weight(p),
+    }
+  }
+}
+
+/// How a brush combines its mosaic with the voxels it touches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mode<Material> {
+  /// Fill the mosaic's volume with its materials.
+  Add,
+  /// Carve the mosaic's volume out, leaving `empty` behind.
+  Subtract {
+    #[allow(missing_docs)]
+    empty: Material,
+  },
+  /// Like `Add`, but only change voxels made of `filter`.
+  Replace {
+    #[allow(missing_docs)]
+    filter: Material,
+  },
+  /// Change the material of non-`empty` voxels inside the mosaic, without changing their shape.
+  Paint {
+    #[allow(missing_docs)]
+    empty: Material,
+  },
+}
@@ -0,0 +1,563 @@
+//! Serializable descriptions of brushes built from the provided fields and mosaics,
+//! so edits can be recorded, sent over the network, and replayed.
+
+use cgmath::{Basis3, InnerSpace, Point3, Quaternion, Vector3};
+
+use brush;
+use field;
+use field::fixed;
+use mosaic;
+use tree;
+
+/// A description of a field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Field {
+  Sphere { radius: f32 },
+  Cuboid { half_extents: [f32; 3], rounding: f32 },
+  Plane { normal: [f32; 3], offset: f32, thickness: f32 },
+  HalfSpace { normal: [f32; 3], offset: f32 },
+  Cylinder { radius: f32, half_height: f32 },
+  Capsule { radius: f32, half_height: f32 },
+  Cone { radius: f32, half_height: f32 },
+  Torus { major_radius: f32, minor_radius: f32 },
+  Translation { translation: [f32; 3], field: Box<Field> },
+  /// A rotation by the quaternion `[s, x, y, z]`.
+  Rotation { rotation: [f32; 4], field: Box<Field> },
+  Scale { scale: [f32; 3], field: Box<Field> },
+  Intersection(Box<Field>, Box<Field>),
+  Union { fields: Vec<Field>, blend: f32 },
+  /// The first field, with the rest cut out of it. There must be at least one field.
+  Difference { fields: Vec<Field>, blend: f32 },
+  Complement(Box<Field>),
+  Twist { rate: f32, field: Box<Field> },
+  Bend { rate: f32, field: Box<Field> },
+  Repeat { period: [f32; 3], copies: Option<[u32; 3]>, field: Box<Field> },
+  Mirror { axes: [bool; 3], field: Box<Field> },
+  Displace { field: Box<Field>, displacement: Box<Field> },
+  Perlin { seed: u32, frequency: f32, amplitude: f32 },
+  Fractal {
+    seed: u32,
+    frequency: f32,
+    amplitude: f32,
+    kind: field::fractal::Kind,
+    octaves: u32,
+    lacunarity: f32,
+    gain: f32,
+  },
+  /// A fixed-point field, which brushes the same voxels on every machine.
+  Deterministic(Fixed),
+}
+
+/// A description of a fixed-point field from `field::fixed`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Fixed {
+  Sphere { radius: fixed::T },
+  Translation { translation: fixed::Point, field: Box<Fixed> },
+  /// A rotation by an orthonormal matrix, given as rows.
+  Rotation { rows: [fixed::Point; 3], field: Box<Fixed> },
+  Intersection(Box<Fixed>, Box<Fixed>),
+}
+
+/// Reasons a description can't be built.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+  /// A union or difference with nothing in it.
+  Empty,
+  /// A number that's infinite or NaN.
+  NotFinite(f32),
+  /// A radius, size, blend or similar that's negative.
+  Negative(f32),
+  /// A normal, rotation, scale or period that's zero.
+  Zero,
+  /// A fractal with more than `field::fractal::MAX_OCTAVES` octaves.
+  TooManyOctaves(u32),
+  /// Brush bounds that are inverted, or outside what a tree can address.
+  BadBounds,
+  /// A brush's `min_lg_size` that's negative or larger than a tree can be.
+  BadLgSize(i16),
+}
+
+fn finite(x: f32) -> Result<f32, Error> {
+  if x.is_finite() { Ok(x) } else { Err(Error::NotFinite(x)) }
+}
+
+fn nonnegative(x: f32) -> Result<f32, Error> {
+  if finite(x)? < 0.0 { Err(Error::Negative(x)) } else { Ok(x) }
+}
+
+fn vector(v: [f32; 3]) -> Result<Vector3<f32>, Error> {
+  Ok(Vector3::new(finite(v[0])?, finite(v[1])?, finite(v[2])?))
+}
+
+fn nonzero(v: [f32; 3]) -> Result<Vector3<f32>, Error> {
+  let v = vector(v)?;
+  if v.x == 0.0 || v.y == 0.0 || v.z == 0.0 { Err(Error::Zero) } else { Ok(v) }
+}
+
+fn direction(v: [f32; 3]) -> Result<Vector3<f32>, Error> {
+  let v = vector(v)?;
+  if v.magnitude2() > 0.0 { Ok(v.normalize()) } else { Err(Error::Zero) }
+}
+
+fn rotation(q: [f32; 4]) -> Result<Basis3<f32>, Error> {
+  let q = Quaternion::new(finite(q[0])?, finite(q[1])?, finite(q[2])?, finite(q[3])?);
+  // `Basis3::from_quaternion` assumes a unit quaternion, and logs may not be exactly normalized.
+  if q.magnitude2() > 0.0 { Ok(Basis3::from_quaternion(&q.normalize())) } else { Err(Error::Zero) }
+}
+
+fn perlin(seed: u32, frequency: f32, amplitude: f32) -> Result<field::perlin::T, Error> {
+  let mut noise = field::perlin::new(seed);
+  noise.frequency = finite(frequency)?;
+  noise.amplitude = finite(amplitude)?;
+  Ok(noise)
+}
+
+impl Fixed {
+  /// Build the described field, or say why it can't be built.
+  pub fn build(&self) -> Result<Box<dyn fixed::Field>, Error> {
+    Ok(match *self {
+      Fixed::Sphere { radius } => {
+        if radius < fixed::T(0) {
+          return Err(Error::Negative(radius.to_f32()))
+        }
+        Box::new(fixed::Sphere { radius: radius })
+      },
+      Fixed::Translation { translation, ref field } => {
+        Box::new(fixed::Translation { translation: translation, field: field.build()? })
+      },
+      Fixed::Rotation { rows, ref field } => Box::new(fixed::Rotation { rows: rows, field: field.build()? }),
+      Fixed::Intersection(ref field1, ref field2) => {
+        Box::new(fixed::Intersection(field1.build()?, field2.build()?))
+      },
+    })
+  }
+}
+
+impl Field {
+  /// Build the described field, or say why it can't be built.
+  pub fn build(&self) -> Result<Box<dyn field::T>, Error> {
+    Ok(match *self {
+      Field::Sphere { radius } => Box::new(field::sphere::T { radius: nonnegative(radius)? }),
+      Field::Cuboid { half_extents, rounding } => {
+        let half_extents = vector(half_extents)?;
+        for &x in &[half_extents.x, half_extents.y, half_extents.z] {
+          nonnegative(x)?;
+        }
+        Box::new(field::cuboid::rounded(half_extents, nonnegative(rounding)?))
+      },
+      Field::Plane { normal, offset, thickness } => {
+        Box::new(field::plane::T {
+          normal: direction(normal)?,
+          offset: finite(offset)?,
+          thickness: nonnegative(thickness)?,
+        })
+      },
+      Field::HalfSpace { normal, offset } => {
+        Box::new(field::half_space::T { normal: direction(normal)?, offset: finite(offset)? })
+      },
+      Field::Cylinder { radius, half_height } => {
+        Box::new(field::cylinder::new(nonnegative(radius)?, nonnegative(half_height)?))
+      },
+      Field::Capsule { radius, half_height } => {
+        Box::new(field::capsule::new(nonnegative(radius)?, nonnegative(half_height)?))
+      },
+      Field::Cone { radius, half_height } => {
+        Box::new(field::cone::new(nonnegative(radius)?, nonnegative(half_height)?))
+      },
+      Field::Torus { major_radius, minor_radius } => {
+        Box::new(field::torus::new(nonnegative(major_radius)?, nonnegative(minor_radius)?))
+      },
+      Field::Translation { translation, ref field } => {
+        Box::new(field::translation::T {
+          translation: vector(translation)?,
+          field: field.build()?,
+        })
+      },
+      Field::Rotation { rotation: q, ref field } => {
+        Box::new(field::rotation::T {
+          rotation: rotation(q)?,
+          field: field.build()?,
+        })
+      },
+      Field::Scale { scale, ref field } => {
+        Box::new(field::scale::T {
+          scale: nonzero(scale)?,
+          field: field.build()?,
+        })
+      },
+      Field::Intersection(ref field1, ref field2) => {
+        Box::new(field::intersection::new(field1.build()?, field2.build()?))
+      },
+      Field::Union { ref fields, blend } => {
+        if fields.is_empty() {
+          return Err(Error::Empty)
+        }
+        let mut union = field::union::smooth(nonnegative(blend)?);
+        for field in fields {
+          union.push(field.build()?);
+        }
+        Box::new(union)
+      },
+      Field::Difference { ref fields, blend } => {
+        if fields.is_empty() {
+          return Err(Error::Empty)
+        }
+        let mut difference = field::difference::smooth(fields[0].build()?, nonnegative(blend)?);
+        for field in &fields[1..] {
+          difference.push(field.build()?);
+        }
+        Box::new(difference)
+      },
+      Field::Complement(ref field) => Box::new(field::complement::T { field: field.build()? }),
+      Field::Twist { rate, ref field } => Box::new(field::twist::T { rate: finite(rate)?, field: field.build()? }),
+      Field::Bend { rate, ref field } => Box::new(field::bend::T { rate: finite(rate)?, field: field.build()? }),
+      Field::Repeat { period, copies, ref field } => {
+        let period = nonzero(period)?;
+        for &x in &[period.x, period.y, period.z] {
+          nonnegative(x)?;
+        }
+        Box::new(field::repeat::T {
+          period: period,
+          copies: copies,
+          field: field.build()?,
+        })
+      },
+      Field::Mirror { axes, ref field } => Box::new(field::mirror::T { axes: axes, field: field.build()? }),
+      Field::Displace { ref field, ref displacement } => {
+        Box::new(field::displace::T {
+          field: field.build()?,
+          displacement: displacement.build()?,
+        })
+      },
+      Field::Perlin { seed, frequency, amplitude } => Box::new(perlin(seed, frequency, amplitude)?),
+      Field::Fractal { seed, frequency, amplitude, kind, octaves, lacunarity, gain } => {
+        if octaves > field::fractal::MAX_OCTAVES {
+          return Err(Error::TooManyOctaves(octaves))
+        }
+        Box::new(field::fractal::T {
+          noise: perlin(seed, frequency, amplitude)?,
+          kind: kind,
+          octaves: octaves,
+          lacunarity: finite(lacunarity)?,
+          gain: finite(gain)?,
+        })
+      },
+      Field::Deterministic(ref field) => Box::new(fixed::Deterministic(field.build()?)),
+    })
+  }
+}
+
+/// A description of a mosaic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Mosaic<Material> {
+  Solid { field: Field, material: Material },
+  Union(Vec<(Field, Material)>),
+  Translation { translation: [f32; 3], mosaic: Box<Mosaic<Material>> },
+  /// A rotation by the quaternion `[s, x, y, z]`.
+  Rotation { rotation: [f32; 4], mosaic: Box<Mosaic<Material>> },
+  Scale { scale: [f32; 3], mosaic: Box<Mosaic<Material>> },
+}
+
+impl<Material> Mosaic<Material> where Material: Eq + Clone + Send + Sync + 'static {
+  /// Build the described mosaic, or say why it can't be built.
+  pub fn build(&self) -> Result<Box<dyn mosaic::T<Material>>, Error> {
+    Ok(match *self {
+      Mosaic::Solid { ref field, ref material } => {
+        Box::new(mosaic::solid::T {
+          field: field.build()?,
+          material: material.clone(),
+        })
+      },
+      Mosaic::Union(ref components) => {
+        if components.is_empty() {
+          return Err(Error::Empty)
+        }
+        let mut union = mosaic::union::new();
+        for (field, material) in components {
+          union.push(material.clone(), field.build()?);
+        }
+        Box::new(union)
+      },
+      Mosaic::Translation { translation, ref mosaic } => {
+        Box::new(mosaic::translation::T {
+          translation: vector(translation)?,
+          mosaic: mosaic.build()?,
+        })
+      },
+      Mosaic::Rotation { rotation: q, ref mosaic } => {
+        Box::new(mosaic::rotation::T {
+          rotation: rotation(q)?,
+          mosaic: mosaic.build()?,
+        })
+      },
+      Mosaic::Scale { scale, ref mosaic } => {
+        Box::new(mosaic::scale::T {
+          scale: nonzero(scale)?,
+          mosaic: mosaic.build()?,
+        })
+      },
+    })
+  }
+}
+
+/// A description of a `brush::Profile`. Custom profiles can't be described.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Profile {
+  Linear,
+  Smoothstep,
+}
+
+/// A description of a `brush::Falloff`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub struct Falloff {
+  pub center: [f32; 3],
+  pub radius: f32,
+  pub profile: Profile,
+}
+
+/// A description of a `brush::T`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub struct Brush<Material> {
+  pub low: [i32; 3],
+  pub high: [i32; 3],
+  pub mosaic: Mosaic<Material>,
+  pub min_lg_size: i16,
+  pub strength: f32,
+  pub falloff: Option<Falloff>,
+}
+
+impl<Material> Brush<Material> where Material: Eq + Clone + Send + Sync + 'static {
+  /// Build the described brush, or say why it can't be built.
+  pub fn build(&self) -> Result<brush::T<Box<dyn mosaic::T<Material>>>, Error> {
+    let falloff =
+      match self.falloff {
+        None => None,
+        Some(ref falloff) => {
+          let center = vector(falloff.center)?;
+          Some(brush::Falloff {
+            center: Point3::new(center.x, center.y, center.z),
+            radius: nonnegative(falloff.radius)?,
+            profile:
+              match falloff.profile {
+                Profile::Linear => brush::Profile::Linear,
+                Profile::Smoothstep => brush::Profile::Smoothstep,
+              },
+          })
+        },
+      };
+    // Commands can come from untrusted peers, so they mustn't ask for unbounded refinement.
+    if self.min_lg_size < 0 || self.min_lg_size > tree::validate::MAX_LG_SIZE as i16 {
+      return Err(Error::BadLgSize(self.min_lg_size))
+    }
+    for i in 0..3 {
+      if self.low[i] > self.high[i] {
+        return Err(Error::BadBounds)
+      }
+    }
+    let addressable = |p: [i32; 3]| tree::validate::is_addressable(&::bounds::new(p[0], p[1], p[2], 0));
+    if !addressable(self.low) || !addressable([self.high[0] - 1, self.high[1] - 1, self.high[2] - 1]) {
+      return Err(Error::BadBounds)
+    }
+    Ok(brush::T {
+      bounds: brush::Bounds::new(Point3::from(self.low), Point3::from(self.high)),
+      mosaic: self.mosaic.build()?,
+      min_lg_size: self.min_lg_size,
+      strength: nonnegative(self.strength)?,
+      falloff: falloff,
+    })
+  }
+}
+
+/// A single edit: a brush, and how to apply it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub struct T<Material> {
+  pub brush: Brush<Material>,
+  pub mode: brush::Mode<Material>,
+}
+
+impl<Material> T<Material> where Material: Eq + Clone + Send + Sync + 'static {
+  /// Apply this edit to a tree. Nothing changes if the brush can't be built.
+  pub fn apply<Voxel, Generate, OnVoxelUpdate>(
+    &self,
+    tree: &mut tree::T<Voxel>,
+    generate: &mut Generate,
+    on_voxel_update: &mut OnVoxelUpdate,
+  ) -> Result<(), Error> where
+    Voxel: ::T<Material>,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+    OnVoxelUpdate: FnMut(&Voxel, &::bounds::T),
+  {
+    tree.brush(&self.brush.build()?, &self.mode, generate, on_voxel_update);
+    Ok(())
+  }
+}
+
+/// Apply a log of edits to a tree, in order, stopping at the first that can't be built.
+/// Replaying the same log onto the same tree always produces the same result.
+pub fn replay<Voxel, Material, Generate, OnVoxelUpdate>(
+  tree: &mut tree::T<Voxel>,
+  log: &[T<Material>],
+  generate: &mut Generate,
+  on_voxel_update: &mut OnVoxelUpdate,
+) -> Result<(), Error> where
+  Material: Eq + Clone + Send + Sync + 'static,
+  Voxel: ::T<Material>,
+  Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+  OnVoxelUpdate: FnMut(&Voxel, &::bounds::T),
+{
+  for command in log {
+    command.apply(tree, generate, on_voxel_update)?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+  use serde::{Serialize, Deserialize};
+
+  use brush;
+  use field;
+  use impls::surface_vertex;
+  use mosaic;
+  use tree;
+
+  use super::*;
+
+  fn is_serializable<X>() where X: Serialize + for<'de> Deserialize<'de> {}
+
+  fn log() -> Vec<T<u8>> {
+    let ball = Field::Translation { translation: [1.0, 0.0, 0.0], field: Box::new(Field::Sphere { radius: 3.0 }) };
+    let brush = |mosaic| {
+      Brush {
+        low: [-4, -4, -4],
+        high: [4, 4, 4],
+        mosaic: mosaic,
+        min_lg_size: 0,
+        strength: 1.0,
+        falloff: None,
+      }
+    };
+    vec!(
+      T { brush: brush(Mosaic::Solid { field: ball.clone(), material: 1 }), mode: brush::Mode::Add },
+      T {
+        brush: brush(Mosaic::Union(vec!((Field::Sphere { radius: 1.5 }, 2)))),
+        mode: brush::Mode::Subtract { empty: 0 },
+      },
+    )
+  }
+
+  #[test]
+  fn replay_is_deterministic() {
+    is_serializable::<T<u8>>();
+
+    let generate = |b: &::bounds::T| if b.lg_size == 0 { Some(surface_vertex::T::Volume(0)) } else { None };
+    let mut replayed = tree::new();
+    replayed.grow_to_hold(&::bounds::new(0, 0, 0, 3));
+    let mut again = tree::new();
+    again.grow_to_hold(&::bounds::new(0, 0, 0, 3));
+    replay(&mut replayed, &log(), &mut |b| generate(b), &mut |_, _| {}).unwrap();
+    replay(&mut again, &log(), &mut |b| generate(b), &mut |_, _| {}).unwrap();
+    assert_eq!(replayed.contents, again.contents);
+
+    // The same edits, made by hand.
+    let mut direct = tree::new();
+    direct.grow_to_hold(&::bounds::new(0, 0, 0, 3));
+    let bounds = brush::Bounds::new(Point3::new(-4, -4, -4), Point3::new(4, 4, 4));
+    let ball =
+      mosaic::solid::T {
+        field: field::translation::T { translation: Vector3::new(1.0, 0.0, 0.0), field: field::sphere::T { radius: 3.0 } },
+        material: 1,
+      };
+    direct.brush(&brush::new(bounds, ball, 0), &brush::Mode::Add, &mut |b| generate(b), &mut |_, _| {});
+    let mut hole = mosaic::union::new();
+    hole.push(2, field::sphere::T { radius: 1.5 });
+    direct.brush(&brush::new(bounds, hole, 0), &brush::Mode::Subtract { empty: 0 }, &mut |b| generate(b), &mut |_, _| {});
+    assert_eq!(replayed.contents, direct.contents);
+  }
+
+  #[test]
+  fn invalid_descriptions_are_rejected() {
+    let sphere = || Box::new(Field::Sphere { radius: 1.0 });
+    let (nan, infinity) = (f32::NAN, f32::INFINITY);
+    let fractal =
+      Field::Fractal {
+        seed: 0,
+        frequency: 1.0,
+        amplitude: 1.0,
+        kind: field::fractal::Kind::Fbm,
+        octaves: 17,
+        lacunarity: 2.0,
+        gain: 0.5,
+      };
+    let invalid = vec!(
+      (Field::Union { fields: vec!(), blend: 0.0 }, Error::Empty),
+      (Field::Difference { fields: vec!(), blend: 0.0 }, Error::Empty),
+      (Field::Union { fields: vec!(*sphere()), blend: -1.0 }, Error::Negative(-1.0)),
+      (Field::Difference { fields: vec!(*sphere()), blend: -1.0 }, Error::Negative(-1.0)),
+      (Field::Sphere { radius: -1.0 }, Error::Negative(-1.0)),
+      (Field::Sphere { radius: infinity }, Error::NotFinite(infinity)),
+      (Field::Cuboid { half_extents: [1.0, -1.0, 1.0], rounding: 0.0 }, Error::Negative(-1.0)),
+      (Field::Cuboid { half_extents: [1.0, 1.0, 1.0], rounding: -1.0 }, Error::Negative(-1.0)),
+      (Field::Plane { normal: [0.0, 0.0, 0.0], offset: 0.0, thickness: 1.0 }, Error::Zero),
+      (Field::Plane { normal: [0.0, 1.0, 0.0], offset: 0.0, thickness: -1.0 }, Error::Negative(-1.0)),
+      (Field::HalfSpace { normal: [0.0, 0.0, 0.0], offset: 0.0 }, Error::Zero),
+      (Field::Cylinder { radius: -1.0, half_height: 1.0 }, Error::Negative(-1.0)),
+      (Field::Capsule { radius: 1.0, half_height: -1.0 }, Error::Negative(-1.0)),
+      (Field::Cone { radius: -1.0, half_height: 1.0 }, Error::Negative(-1.0)),
+      (Field::Torus { major_radius: 2.0, minor_radius: -1.0 }, Error::Negative(-1.0)),
+      (Field::Translation { translation: [0.0, -infinity, 0.0], field: sphere() }, Error::NotFinite(-infinity)),
+      (Field::Rotation { rotation: [0.0, 0.0, 0.0, 0.0], field: sphere() }, Error::Zero),
+      (Field::Scale { scale: [1.0, 0.0, 1.0], field: sphere() }, Error::Zero),
+      (Field::Repeat { period: [1.0, -1.0, 1.0], copies: None, field: sphere() }, Error::Negative(-1.0)),
+      (Field::Complement(Box::new(Field::Sphere { radius: -1.0 })), Error::Negative(-1.0)),
+      (Field::Intersection(sphere(), Box::new(Field::Union { fields: vec!(), blend: 0.0 })), Error::Empty),
+      (fractal, Error::TooManyOctaves(17)),
+    );
+    for (field, error) in invalid {
+      assert_eq!(field.build().err(), Some(error), "{:?}", field);
+    }
+    match (Field::Twist { rate: nan, field: sphere() }).build() {
+      Err(Error::NotFinite(x)) => assert!(x.is_nan()),
+      result => panic!("expected a NaN to be rejected, got {:?}", result.err()),
+    }
+
+    assert_eq!(Mosaic::Union::<u8>(vec!()).build().err(), Some(Error::Empty));
+    let solid = Mosaic::Solid { field: *sphere(), material: 1u8 };
+    let flat = Mosaic::Scale { scale: [0.0, 1.0, 1.0], mosaic: Box::new(solid.clone()) };
+    assert_eq!(flat.build().err(), Some(Error::Zero));
+    let brush =
+      Brush {
+        low: [-1, -1, -1],
+        high: [1, 1, 1],
+        mosaic: solid,
+        min_lg_size: 0,
+        strength: 1.0,
+        falloff: Some(Falloff { center: [0.0, 0.0, 0.0], radius: -1.0, profile: Profile::Linear }),
+      };
+    assert_eq!(brush.build().err(), Some(Error::Negative(-1.0)));
+    assert!(Brush { falloff: None, ..brush.clone() }.build().is_ok());
+    assert_eq!(Brush { falloff: None, strength: -0.5, ..brush.clone() }.build().err(), Some(Error::Negative(-0.5)));
+    let brush = Brush { falloff: None, ..brush };
+    assert_eq!(Brush { min_lg_size: -1, ..brush.clone() }.build().err(), Some(Error::BadLgSize(-1)));
+    assert_eq!(Brush { low: [2, -1, -1], ..brush.clone() }.build().err(), Some(Error::BadBounds));
+    assert_eq!(Brush { high: [1, 1, 1 << 30 | 1], ..brush.clone() }.build().err(), Some(Error::BadBounds));
+    assert_eq!(Brush { low: [i32::MIN, -1, -1], ..brush }.build().err(), Some(Error::BadBounds));
+  }
+
+  #[test]
+  fn rotations_are_normalized() {
+    let ball = Field::Translation { translation: [0.0, 2.0, 0.0], field: Box::new(Field::Sphere { radius: 1.0 }) };
+    // A quarter turn around z, scaled up.
+    let s = 2.0 * 0.5f32.sqrt();
+    let turned = Field::Rotation { rotation: [s, 0.0, 0.0, s], field: Box::new(ball) }.build().unwrap();
+    assert!((turned.density(&Point3::new(-2.0, 0.0, 0.0)) - 1.0).abs() < 1e-5);
+  }
+}
@@ -0,0 +1,135 @@
+//! A field defined by applying an arbitrary affine transformation to another field.
+
+use cgmath::{Point3, Vector3, Matrix, Matrix3, Matrix4, Decomposed, InnerSpace, Rotation3, SquareMatrix, Transform};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  transform: Matrix4<f32>,
+  inverse: Matrix4<f32>,
+  // The inverse transpose of the linear part of `transform`.
+  normal_transform: Matrix3<f32>,
+  density_scale: f32,
+  pub field: Field,
+}
+
+fn linear_part(m: &Matrix4<f32>) -> Matrix3<f32> {
+  Matrix3::from_cols(m.x.truncate(), m.y.truncate(), m.z.truncate())
+}
+
+/// Transform a field by `transform`, or `None` if it isn't invertible.
+pub fn new<Field>(transform: Matrix4<f32>, field: Field) -> Option<T<Field>> {
+  let inverse = transform.invert()?;
+  let inverse_linear = linear_part(&inverse);
+  // Densities are divided by an upper bound on how much the inverse can stretch distances, so that
+  // e.g. a signed distance stays a lower bound on the distance to the surface.
+  let frobenius =
+    (inverse_linear.x.magnitude2() + inverse_linear.y.magnitude2() + inverse_linear.z.magnitude2()).sqrt();
+  let column_sum = |v: Vector3<f32>| v.x.abs() + v.y.abs() + v.z.abs();
+  let max_column_sum = column_sum(inverse_linear.x).max(column_sum(inverse_linear.y)).max(column_sum(inverse_linear.z));
+  let transposed = inverse_linear.transpose();
+  let max_row_sum = column_sum(transposed.x).max(column_sum(transposed.y)).max(column_sum(transposed.z));
+  let stretch = frobenius.min((max_column_sum * max_row_sum).sqrt());
+  Some(T {
+    transform: transform,
+    inverse: inverse,
+    normal_transform: transposed,
+    density_scale: 1.0 / stretch,
+    field: field,
+  })
+}
+
+/// Transform a field by a scale, rotation and translation, or `None` if the scale is zero.
+pub fn of_decomposed<Field, Rotation>(transform: Decomposed<Vector3<f32>, Rotation>, field: Field) -> Option<T<Field>>
+  where Rotation: Rotation3<f32> + Into<Matrix3<f32>>,
+{
+  let scale = transform.scale.abs();
+  let mut t = new(Matrix4::from(transform), field)?;
+  // Rotations don't stretch distances, so the scale is exactly how much distances change.
+  t.density_scale = scale;
+  Some(t)
+}
+
+impl<Field> T<Field> {
+  #[allow(missing_docs)]
+  pub fn transform(&self) -> &Matrix4<f32> {
+    &self.transform
+  }
+
+  /// The point in the untransformed field corresponding to `p`.
+  pub fn to_local(&self, p: &Point3<f32>) -> Point3<f32> {
+    self.inverse.transform_point(*p)
+  }
+
+  /// What the untransformed field's densities are multiplied by.
+  pub fn density_scale(&self) -> f32 {
+    self.density_scale
+  }
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.density_scale * field::T::density(&self.field, &self.inverse.transform_point(*p))
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let n = field::T::normal(&self.field, &self.inverse.transform_point(*p));
+    (self.normal_transform * n).normalize()
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (low, high) = field::transform_box(low, high, |p| self.inverse.transform_point(p));
+    let (min, max) = field::T::density_bounds(&self.field, &low, &high)?;
+    Some((self.density_scale * min, self.density_scale * max))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, Matrix4, Decomposed, Quaternion, InnerSpace};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn sheared_fields() {
+    let ground = field::half_space::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    // Shear x along y, which tilts the ground.
+    let shear = Matrix4::new(
+      1.0, 0.0, 0.0, 0.0,
+      1.0, 1.0, 0.0, 0.0,
+      0.0, 0.0, 1.0, 0.0,
+      0.0, 0.0, 0.0, 1.0,
+    );
+    let tilted = super::new(shear, ground).unwrap();
+    assert!(tilted.density(&Point3::new(0.0, -0.1, 0.0)) > 0.0);
+    assert!(tilted.density(&Point3::new(5.0, 0.1, 0.0)) < 0.0);
+    // The ground is still y = 0 after a shear along it, so its normal is unchanged.
+    assert!((tilted.normal(&Point3::new(3.0, 0.0, 0.0)) - Vector3::new(0.0, 1.0, 0.0)).magnitude2() < 1e-6);
+
+    let shear = Matrix4::new(
+      1.0, 1.0, 0.0, 0.0,
+      0.0, 1.0, 0.0, 0.0,
+      0.0, 0.0, 1.0, 0.0,
+      0.0, 0.0, 0.0, 1.0,
+    );
+    // Now the ground is the plane y = x, facing up and to the left.
+    let tilted = super::new(shear, ground).unwrap();
+    assert!(tilted.density(&Point3::new(1.0, 0.9, 0.0)) > 0.0);
+    assert!(tilted.density(&Point3::new(1.0, 1.1, 0.0)) < 0.0);
+    assert!((tilted.normal(&Point3::new(1.0, 1.0, 0.0)) - Vector3::new(-1.0, 1.0, 0.0).normalize()).magnitude2() < 1e-6);
+
+    assert!(super::new(Matrix4::from_scale(0.0), ground).is_none());
+
+    let moved =
+      super::of_decomposed(
+        Decomposed { scale: 2.0, rot: Quaternion::new(1.0, 0.0, 0.0, 0.0), disp: Vector3::new(1.0, 0.0, 0.0) },
+        field::sphere::T { radius: 1.0 },
+      ).unwrap();
+    assert_eq!(moved.density(&Point3::new(3.0, 0.0, 0.0)), 0.0);
+    assert_eq!(moved.density(&Point3::new(1.0, 0.0, 0.0)), 2.0);
+    assert_eq!(moved.normal(&Point3::new(3.0, 0.0, 0.0)), Vector3::new(1.0, 0.0, 0.0));
+  }
+}
@@ -0,0 +1,111 @@
+//! A field defined by bending another field upwards along the x axis.
+
+use cgmath::{Point3, Vector3};
+use std::f32;
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  /// The curvature of the bend: the x axis becomes a circle of radius `1 / rate` that touches
+  /// it at the origin and curves towards +y.
+  pub rate: f32,
+  pub field: Field,
+}
+
+impl<Field> T<Field> {
+  // The radius of the bend, and which way it curves: 1 for +y, -1 for -y.
+  fn radius(&self) -> (f32, f32) {
+    (1.0 / self.rate.abs(), self.rate.signum())
+  }
+
+  fn unbend(&self, p: &Point3<f32>) -> Point3<f32> {
+    if self.rate == 0.0 {
+      return *p
+    }
+    // Measure from the centre of the circle the x axis is bent into.
+    let (radius, sign) = self.radius();
+    let (x, y) = (p.x, radius - sign * p.y);
+    let angle = x.atan2(y);
+    let distance = (x*x + y*y).sqrt();
+    Point3::new(angle * radius, sign * (radius - distance), p.z)
+  }
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field, &self.unbend(p))
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::numerical_normal(self, p, field::NORMAL_STEP)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    if self.rate == 0.0 {
+      return field::T::density_bounds(&self.field, low, high)
+    }
+    // In coordinates around the centre of the circle, the box unbends to a range of angles and
+    // distances. If it reaches the centre, every angle is possible.
+    let (radius, sign) = self.radius();
+    let (y0, y1) = (radius - sign * low.y, radius - sign * high.y);
+    let (y_min, y_max) = (y0.min(y1), y0.max(y1));
+    if y_min <= 0.0 {
+      return None
+    }
+    let mut angles = (f32::INFINITY, f32::NEG_INFINITY);
+    for &x in &[low.x, high.x] {
+      for &y in &[y_min, y_max] {
+        let angle = x.atan2(y);
+        angles = (angles.0.min(angle), angles.1.max(angle));
+      }
+    }
+    let nearest_x = if low.x > 0.0 { low.x } else if high.x < 0.0 { high.x } else { 0.0 };
+    let nearest = (nearest_x*nearest_x + y_min*y_min).sqrt();
+    let farthest_x = f32::max(low.x.abs(), high.x.abs());
+    let farthest = (farthest_x*farthest_x + y_max*y_max).sqrt();
+
+    let (y0, y1) = (sign * (radius - farthest), sign * (radius - nearest));
+    field::T::density_bounds(
+      &self.field,
+      &Point3::new(angles.0 * radius, y0.min(y1), low.z),
+      &Point3::new(angles.1 * radius, y0.max(y1), high.z),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn bends() {
+    // A bar along x, bent up at its ends.
+    let bend = super::T { rate: 0.5, field: field::cuboid::new(Vector3::new(3.0, 0.25, 0.25)) };
+    assert!(bend.density(&Point3::new(0.0, 0.0, 0.0)) > 0.0);
+    let end = Point3::new(2.0 * 1.0f32.sin(), 2.0 * (1.0 - 1.0f32.cos()), 0.0);
+    assert!(bend.density(&end) > 0.0);
+    assert!(bend.density(&Point3::new(2.0, 0.0, 0.0)) < 0.0);
+    let n = bend.normal(&Point3::new(0.0, 0.25, 0.0));
+    assert!((n - Vector3::new(0.0, 1.0, 0.0)).magnitude2() < 1e-3);
+
+    // Bending the other way mirrors the bar.
+    let down = super::T { rate: -0.5, .. bend };
+    assert_eq!(down.density(&Point3::new(0.0, 0.0, 0.0)), bend.density(&Point3::new(0.0, 0.0, 0.0)));
+    assert_eq!(down.density(&Point3::new(end.x, -end.y, 0.0)), bend.density(&end));
+
+    for field in &[bend, down] {
+      let (low, high) = (Point3::new(1.0, -0.5, -0.5), Point3::new(2.0, 0.5, 0.5));
+      let (min, max) = field.density_bounds(&low, &high).unwrap();
+      for &p in &[low, high, Point3::new(1.5, 0.0, 0.0), Point3::new(2.0, -0.5, 0.0), Point3::new(1.0, 0.5, 0.0)] {
+        let d = field.density(&p);
+        assert!(min <= d && d <= max);
+      }
+    }
+    assert_eq!(bend.density_bounds(&Point3::new(-1.0, 1.0, -1.0), &Point3::new(1.0, 3.0, 1.0)), None);
+  }
+}
@@ -0,0 +1,154 @@
+//! A cache of another field's values.
+//!
+//! A cache can be shared between threads, but they contend for it, so it's usually faster to
+//! create one for each thread evaluating a shared field. The field is evaluated outside the lock,
+//! so two threads may both evaluate a point that isn't cached yet.
+//!
+//! Each kind of value holds at most `capacity` points; once it's full, it's forgotten and starts
+//! again, so memory stays bounded however many points are evaluated.
+
+use cgmath::{Point3, Vector3};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use field;
+use mosaic;
+
+type Key = (u32, u32, u32);
+
+fn key(p: &Point3<f32>) -> Key {
+  (p.x.to_bits(), p.y.to_bits(), p.z.to_bits())
+}
+
+/// How many points each kind of value is cached for by default.
+pub const DEFAULT_CAPACITY: usize = 1 << 16;
+
+// Look a value up, or compute and remember it. The lock isn't held while computing.
+fn cached<Value, Compute>(map: &Mutex<HashMap<Key, Value>>, capacity: usize, p: &Point3<f32>, compute: Compute) -> Value
+  where Value: Copy, Compute: FnOnce() -> Value
+{
+  let key = key(p);
+  if let Some(value) = map.lock().unwrap().get(&key) {
+    return *value
+  }
+  let value = compute();
+  let mut map = map.lock().unwrap();
+  if map.len() >= capacity {
+    map.clear();
+  }
+  map.insert(key, value);
+  value
+}
+
+#[allow(missing_docs)]
+pub struct T<'a, Field: 'a + ?Sized> {
+  pub field: &'a Field,
+  capacity: usize,
+  densities: Mutex<HashMap<Key, f32>>,
+  normals: Mutex<HashMap<Key, Vector3<f32>>>,
+  mosaic_densities: Mutex<HashMap<Key, f32>>,
+}
+
+/// Cache the values of `field` at the points it's evaluated at.
+pub fn new<'a, Field: ?Sized>(field: &'a Field) -> T<'a, Field> {
+  with_capacity(field, DEFAULT_CAPACITY)
+}
+
+/// Cache the values of `field` at up to `capacity` points at a time.
+pub fn with_capacity<'a, Field: ?Sized>(field: &'a Field, capacity: usize) -> T<'a, Field> {
+  assert!(capacity > 0);
+  T {
+    field: field,
+    capacity: capacity,
+    densities: Mutex::new(HashMap::new()),
+    normals: Mutex::new(HashMap::new()),
+    mosaic_densities: Mutex::new(HashMap::new()),
+  }
+}
+
+impl<'a, Field: ?Sized> T<'a, Field> {
+  /// Forget every cached value.
+  pub fn clear(&self) {
+    self.densities.lock().unwrap().clear();
+    self.normals.lock().unwrap().clear();
+    self.mosaic_densities.lock().unwrap().clear();
+  }
+}
+
+impl<'a, Field: ?Sized> field::T for T<'a, Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    cached(&self.densities, self.capacity, p, || self.field.density(p))
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    cached(&self.normals, self.capacity, p, || self.field.normal(p))
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    self.field.density_bounds(low, high)
+  }
+}
+
+impl<'a, Field: ?Sized, Material> mosaic::T<Material> for T<'a, Field> where Field: mosaic::T<Material> {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    cached(&self.mosaic_densities, self.capacity, p, || mosaic::T::density(self.field, p))
+  }
+
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
+    mosaic::T::material(self.field, p)
+  }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    mosaic::T::homogeneous(self.field, low, high)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use field;
+
+  struct Counting(AtomicUsize);
+
+  impl field::T for Counting {
+    fn density(&self, p: &Point3<f32>) -> f32 {
+      self.0.fetch_add(1, Ordering::SeqCst);
+      p.x
+    }
+
+    fn normal(&self, _: &Point3<f32>) -> Vector3<f32> {
+      Vector3::new(1.0, 0.0, 0.0)
+    }
+  }
+
+  #[test]
+  fn cache_evaluates_each_point_once() {
+    let field = Counting(AtomicUsize::new(0));
+    let cache = super::new(&field);
+    for _ in 0..3 {
+      assert_eq!(field::T::density(&cache, &Point3::new(1.0, 2.0, 3.0)), 1.0);
+      assert_eq!(field::T::density(&cache, &Point3::new(2.0, 2.0, 3.0)), 2.0);
+    }
+    assert_eq!(field.0.load(Ordering::SeqCst), 2);
+
+    cache.clear();
+    field::T::density(&cache, &Point3::new(1.0, 2.0, 3.0));
+    assert_eq!(field.0.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn full_caches_start_again() {
+    let field = Counting(AtomicUsize::new(0));
+    let cache = super::with_capacity(&field, 2);
+    for i in 0..3 {
+      field::T::density(&cache, &Point3::new(i as f32, 0.0, 0.0));
+    }
+    assert_eq!(cache.densities.lock().unwrap().len(), 1);
+    field::T::density(&cache, &Point3::new(2.0, 0.0, 0.0));
+    assert_eq!(field.0.load(Ordering::SeqCst), 3);
+    field::T::density(&cache, &Point3::new(0.0, 0.0, 0.0));
+    assert_eq!(field.0.load(Ordering::SeqCst), 4);
+  }
+}
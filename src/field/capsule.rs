@@ -0,0 +1,72 @@
+//! A capsule field: the points within some radius of a line segment on the y axis.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  pub radius: f32,
+  /// Half the length of the segment. It runs from `y = -half_height` to `y = half_height`.
+  pub half_height: f32,
+}
+
+#[allow(missing_docs)]
+pub fn new(radius: f32, half_height: f32) -> T {
+  T {
+    radius: radius,
+    half_height: half_height,
+  }
+}
+
+/// A capsule filled with a single material.
+pub fn solid<Material>(radius: f32, half_height: f32, material: Material) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(radius, half_height),
+    material: material,
+  }
+}
+
+impl T {
+  // The offset of a point from the nearest point on the segment.
+  fn offset(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let y = p.y.max(-self.half_height).min(self.half_height);
+    Vector3::new(p.x, p.y - y, p.z)
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.radius - self.offset(p).magnitude()
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let offset = self.offset(p);
+    // Every direction is equally good on the segment itself.
+    if offset.magnitude2() > 0.0 { offset.normalize() } else { Vector3::new(1.0, 0.0, 0.0) }
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    Some(field::distance_bounds(self, low, high))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field::T;
+
+  #[test]
+  fn capsules() {
+    let pipe = super::new(1.0, 2.0);
+    assert_eq!(pipe.density(&Point3::new(0.0, 1.5, 0.0)), 1.0);
+    assert_eq!(pipe.density(&Point3::new(3.0, 1.5, 0.0)), -2.0);
+    assert_eq!(pipe.density(&Point3::new(0.0, 5.0, 4.0)), -4.0);
+    assert_eq!(pipe.normal(&Point3::new(0.0, -1.0, -0.5)), Vector3::new(0.0, 0.0, -1.0));
+    assert_eq!(pipe.normal(&Point3::new(0.0, 5.0, 4.0)), Vector3::new(0.0, 0.6, 0.8));
+    assert_eq!(pipe.normal(&Point3::new(0.0, 1.0, 0.0)), Vector3::new(1.0, 0.0, 0.0));
+  }
+}
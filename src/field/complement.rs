@@ -0,0 +1,26 @@
+//! A field that's inside wherever another field is outside.
+
+use cgmath::{Point3, Vector3};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  pub field: Field,
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    -field::T::density(&self.field, p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    -field::T::normal(&self.field, p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (min, max) = field::T::density_bounds(&self.field, low, high)?;
+    Some((-max, -min))
+  }
+}
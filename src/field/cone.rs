@@ -0,0 +1,121 @@
+//! A capped cone field around the y axis, with its base below its tip.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+use cgmath::Vector2;
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  /// The radius of the base.
+  pub radius: f32,
+  /// The base is at `y = -half_height` and the tip at `y = half_height`.
+  pub half_height: f32,
+}
+
+#[allow(missing_docs)]
+pub fn new(radius: f32, half_height: f32) -> T {
+  T {
+    radius: radius,
+    half_height: half_height,
+  }
+}
+
+/// A cone filled with a single material.
+pub fn solid<Material>(radius: f32, half_height: f32, material: Material) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(radius, half_height),
+    material: material,
+  }
+}
+
+fn closest_on_segment(a: Vector2<f32>, b: Vector2<f32>, q: Vector2<f32>) -> Vector2<f32> {
+  let ab = b - a;
+  // Flat or needle-thin cones have zero-length faces.
+  if ab.magnitude2() == 0.0 {
+    return a
+  }
+  let t = (q - a).dot(ab) / ab.magnitude2();
+  a + ab * t.clamp(0.0, 1.0)
+}
+
+impl T {
+  // The signed distance to the surface, and the outward normal, in the plane through the axis.
+  fn profile(&self, p: &Point3<f32>) -> (f32, Vector2<f32>) {
+    let h = self.half_height;
+    let q = Vector2::new(field::radial_distance(p), p.y);
+    let base = (Vector2::new(0.0, -h), Vector2::new(self.radius, -h), Vector2::new(0.0, -1.0));
+    let slope = Vector2::new(2.0 * h, self.radius);
+    let slope = if slope.magnitude2() > 0.0 { slope.normalize() } else { Vector2::new(0.0, 1.0) };
+    let side = (Vector2::new(self.radius, -h), Vector2::new(0.0, h), slope);
+
+    let inside = q.y > -h && q.x * 2.0 * h < self.radius * (h - q.y);
+    let mut nearest = None;
+    for &(a, b, face_normal) in &[base, side] {
+      let offset = q - closest_on_segment(a, b, q);
+      let distance = offset.magnitude();
+      match nearest {
+        Some((d, _, _)) if d <= distance => {},
+        _ => nearest = Some((distance, offset, face_normal)),
+      }
+    }
+    let (distance, offset, face_normal) = nearest.unwrap();
+
+    let normal =
+      if distance == 0.0 || inside {
+        face_normal
+      } else {
+        offset / distance
+      };
+    (if inside { distance } else { -distance }, normal)
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.profile(p).0
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let n = self.profile(p).1;
+    field::revolved_normal(p, n.x, n.y)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    Some(field::distance_bounds(self, low, high))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{InnerSpace, Point3, Vector3};
+
+  use field::T;
+
+  #[test]
+  fn cones() {
+    let spire = super::new(3.0, 2.0);
+    assert_eq!(spire.density(&Point3::new(0.0, -1.5, 0.0)), 0.5);
+    assert_eq!(spire.density(&Point3::new(0.0, -4.0, 1.0)), -2.0);
+    assert_eq!(spire.density(&Point3::new(0.0, 5.0, 0.0)), -3.0);
+    assert_eq!(spire.density(&Point3::new(-5.0, -2.0, 0.0)), -2.0);
+    assert_eq!(spire.normal(&Point3::new(0.0, -1.5, 0.0)), Vector3::new(0.0, -1.0, 0.0));
+    assert_eq!(spire.normal(&Point3::new(0.0, 5.0, 0.0)), Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(spire.normal(&Point3::new(0.0, 0.0, 1.5)), Vector3::new(0.0, 0.6, 0.8));
+    assert!(spire.density(&Point3::new(0.0, 0.0, 1.4)) > 0.0);
+    assert!(spire.density(&Point3::new(1.6, 0.0, 0.0)) < 0.0);
+  }
+
+  #[test]
+  fn degenerate_cones() {
+    for &(radius, half_height) in &[(0.0, 2.0), (3.0, 0.0), (0.0, 0.0)] {
+      let cone = super::new(radius, half_height);
+      for p in &[Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, -1.0, 0.5), Point3::new(0.0, 3.0, 0.0)] {
+        assert!(cone.density(p).is_finite());
+        assert!((cone.normal(p).magnitude() - 1.0).abs() < 1e-5);
+      }
+    }
+  }
+}
@@ -0,0 +1,119 @@
+//! An axis-aligned box field, optionally with rounded edges.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  /// Half the size of the box along each axis.
+  pub half_extents: Vector3<f32>,
+  /// The radius of the rounded edges and corners. The box is still `half_extents` in size.
+  pub rounding: f32,
+}
+
+/// A box with sharp edges.
+pub fn new(half_extents: Vector3<f32>) -> T {
+  T {
+    half_extents: half_extents,
+    rounding: 0.0,
+  }
+}
+
+/// A box with sharp edges, filled with a single material.
+pub fn solid<Material>(half_extents: Vector3<f32>, material: Material) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(half_extents),
+    material: material,
+  }
+}
+
+/// A box whose edges and corners are rounded with the given radius.
+pub fn rounded(half_extents: Vector3<f32>, rounding: f32) -> T {
+  T {
+    half_extents: half_extents,
+    rounding: rounding,
+  }
+}
+
+/// A box with rounded edges, filled with a single material.
+pub fn rounded_solid<Material>(
+  half_extents: Vector3<f32>,
+  rounding: f32,
+  material: Material,
+) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: rounded(half_extents, rounding),
+    material: material,
+  }
+}
+
+impl T {
+  // How far each coordinate is outside the box, with the rounding removed.
+  fn excess(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let r = self.rounding;
+    Vector3::new(
+      p.x.abs() - (self.half_extents.x - r),
+      p.y.abs() - (self.half_extents.y - r),
+      p.z.abs() - (self.half_extents.z - r),
+    )
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    let q = self.excess(p);
+    let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+    let inside = q.x.max(q.y).max(q.z).min(0.0);
+    self.rounding - outside.magnitude() - inside
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let q = self.excess(p);
+    let sign = |x: f32| if x < 0.0 { -1.0 } else { 1.0 };
+    if q.x > 0.0 || q.y > 0.0 || q.z > 0.0 {
+      let outside = Vector3::new(q.x.max(0.0) * sign(p.x), q.y.max(0.0) * sign(p.y), q.z.max(0.0) * sign(p.z));
+      return outside.normalize()
+    }
+    // Inside, the nearest face is the one the point is least far inside.
+    if q.x >= q.y && q.x >= q.z {
+      Vector3::new(sign(p.x), 0.0, 0.0)
+    } else if q.y >= q.z {
+      Vector3::new(0.0, sign(p.y), 0.0)
+    } else {
+      Vector3::new(0.0, 0.0, sign(p.z))
+    }
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    Some(field::distance_bounds(self, low, high))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use field::T;
+
+  #[test]
+  fn rounded_boxes() {
+    let sharp = super::new(Vector3::new(2.0, 1.0, 1.0));
+    assert_eq!(sharp.density(&Point3::new(0.0, 0.0, 0.0)), 1.0);
+    assert_eq!(sharp.density(&Point3::new(3.0, 0.0, 0.0)), -1.0);
+    assert_eq!(sharp.normal(&Point3::new(-1.5, 0.0, 0.0)), Vector3::new(-1.0, 0.0, 0.0));
+    assert_eq!(sharp.normal(&Point3::new(0.0, 0.0, 1.5)), Vector3::new(0.0, 0.0, 1.0));
+    assert_eq!(sharp.density(&Point3::new(2.0, 1.0, 1.0)), 0.0);
+
+    let rounded = super::rounded(Vector3::new(2.0, 1.0, 1.0), 0.5);
+    assert_eq!(rounded.density(&Point3::new(2.0, 0.0, 0.0)), 0.0);
+    assert!(rounded.density(&Point3::new(2.0, 1.0, 1.0)) < 0.0);
+    let n = rounded.normal(&Point3::new(2.0, 1.0, 0.0));
+    assert!((n - Vector3::new(0.5f32.sqrt(), 0.5f32.sqrt(), 0.0)).magnitude2() < 1e-6);
+
+    let (min, max) = rounded.density_bounds(&Point3::new(1.0, -1.0, -1.0), &Point3::new(3.0, 1.0, 1.0)).unwrap();
+    assert!(min <= -1.0 && max >= 1.0);
+  }
+}
@@ -0,0 +1,90 @@
+//! A capped cylinder field around the y axis.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+use cgmath::Vector2;
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  pub radius: f32,
+  /// Half the length of the cylinder. It runs from `y = -half_height` to `y = half_height`.
+  pub half_height: f32,
+}
+
+#[allow(missing_docs)]
+pub fn new(radius: f32, half_height: f32) -> T {
+  T {
+    radius: radius,
+    half_height: half_height,
+  }
+}
+
+/// A cylinder filled with a single material.
+pub fn solid<Material>(radius: f32, half_height: f32, material: Material) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(radius, half_height),
+    material: material,
+  }
+}
+
+impl T {
+  // How far outside the side and the caps a point is.
+  fn excess(&self, p: &Point3<f32>) -> Vector2<f32> {
+    Vector2::new(field::radial_distance(p) - self.radius, p.y.abs() - self.half_height)
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    let q = self.excess(p);
+    let outside = Vector2::new(q.x.max(0.0), q.y.max(0.0));
+    let inside = q.x.max(q.y).min(0.0);
+    -outside.magnitude() - inside
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let q = self.excess(p);
+    let sign = if p.y < 0.0 { -1.0 } else { 1.0 };
+    if q.x > 0.0 || q.y > 0.0 {
+      let n = Vector2::new(q.x.max(0.0), q.y.max(0.0) * sign).normalize();
+      field::revolved_normal(p, n.x, n.y)
+    } else if q.x >= q.y {
+      field::revolved_normal(p, 1.0, 0.0)
+    } else {
+      Vector3::new(0.0, sign, 0.0)
+    }
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    Some(field::distance_bounds(self, low, high))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{InnerSpace, Point3, Vector3};
+
+  use field::T;
+
+  #[test]
+  fn cylinders() {
+    let pillar = super::new(1.0, 3.0);
+    assert_eq!(pillar.density(&Point3::new(0.0, 2.0, 0.0)), 1.0);
+    assert_eq!(pillar.density(&Point3::new(0.0, 0.0, 2.0)), -1.0);
+    assert_eq!(pillar.density(&Point3::new(0.0, -5.0, 0.0)), -2.0);
+    assert_eq!(pillar.density(&Point3::new(4.0, 7.0, 0.0)), -5.0);
+    assert_eq!(pillar.normal(&Point3::new(0.0, 0.0, -0.5)), Vector3::new(0.0, 0.0, -1.0));
+    assert_eq!(pillar.normal(&Point3::new(0.0, -2.5, 0.0)), Vector3::new(0.0, -1.0, 0.0));
+    assert_eq!(pillar.normal(&Point3::new(4.0, 7.0, 0.0)), Vector3::new(0.6, 0.8, 0.0));
+  }
+
+  #[test]
+  fn normals_on_the_axis() {
+    let pillar = super::new(1.0, 3.0);
+    assert_eq!(pillar.normal(&Point3::new(0.0, 0.0, 0.0)), Vector3::new(1.0, 0.0, 0.0));
+    assert!((pillar.normal(&Point3::new(0.0, 0.0, 0.0)).magnitude() - 1.0).abs() < 1e-6);
+  }
+}
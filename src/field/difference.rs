@@ -0,0 +1,102 @@
+//! A field with other fields cut out of it, optionally with smoothly blended edges.
+
+use cgmath::{Point3, Vector3};
+use std::f32;
+
+use field;
+
+#[allow(missing_docs)]
+pub struct T {
+  field: Box<dyn field::T>,
+  subtracted: Vec<Box<dyn field::T>>,
+  /// How close the densities of two fields have to be for them to be blended. Zero gives a
+  /// sharp difference.
+  pub blend: f32,
+}
+
+/// A field with nothing cut out of it yet.
+pub fn new<Field>(field: Field) -> T
+  where Field: field::T + 'static,
+{
+  smooth(field, 0.0)
+}
+
+/// A field with nothing cut out of it yet, which will be blended with whatever is cut out;
+/// see `field::smooth_min`.
+pub fn smooth<Field>(field: Field, blend: f32) -> T
+  where Field: field::T + 'static,
+{
+  T {
+    field: Box::new(field),
+    subtracted: Vec::new(),
+    blend: blend,
+  }
+}
+
+impl T {
+  /// Cut a field out.
+  pub fn push<Field>(&mut self, field: Field)
+    where Field: field::T + 'static,
+  {
+    self.subtracted.push(Box::new(field));
+  }
+
+  fn sample(&self, p: &Point3<f32>) -> field::Sample {
+    let first = (self.field.density(p), self.field.normal(p));
+    self.subtracted.iter().fold(first, |sample, field| {
+      field::smooth_min(sample, (-field.density(p), -field.normal(p)), self.blend)
+    })
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    if self.blend <= 0.0 {
+      return self.subtracted.iter().fold(self.field.density(p), |min, field| f32::min(min, -field.density(p)))
+    }
+    self.sample(p).0
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    self.sample(p).1
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let mut bounds = self.field.density_bounds(low, high)?;
+    for field in &self.subtracted {
+      let (min, max) = field.density_bounds(low, high)?;
+      bounds = (f32::min(bounds.0, -max), f32::min(bounds.1, -min));
+    }
+    // Each blend lowers the density by at most a quarter of the blend radius.
+    let lower = self.blend.max(0.0) / 4.0 * self.subtracted.len() as f32;
+    Some((bounds.0 - lower, bounds.1))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn differences() {
+    let mut bowl = super::new(field::sphere::T { radius: 2.0 });
+    bowl.push(field::translation::T { translation: Vector3::new(0.0, 1.0, 0.0), field: field::sphere::T { radius: 2.0 } });
+    assert!(bowl.density(&Point3::new(0.0, -1.5, 0.0)) > 0.0);
+    assert!(bowl.density(&Point3::new(0.0, 0.0, 0.0)) < 0.0);
+    // Inside the hole, the surface faces into it.
+    assert_eq!(bowl.normal(&Point3::new(0.0, -0.5, 0.0)), Vector3::new(0.0, 1.0, 0.0));
+
+    let mut smooth = super::smooth(field::sphere::T { radius: 2.0 }, 1.0);
+    smooth.push(field::translation::T { translation: Vector3::new(0.0, 1.0, 0.0), field: field::sphere::T { radius: 2.0 } });
+    let p = Point3::new(0.0, -1.5, 0.0);
+    assert!(smooth.density(&p) < bowl.density(&p));
+    assert_eq!(smooth.density(&Point3::new(0.0, 0.0, 5.0)), bowl.density(&Point3::new(0.0, 0.0, 5.0)));
+
+    let hollow = field::complement::T { field: field::sphere::T { radius: 1.0 } };
+    assert_eq!(hollow.density(&Point3::new(0.0, 2.0, 0.0)), 3.0);
+    assert_eq!(hollow.normal(&Point3::new(0.0, 2.0, 0.0)), Vector3::new(0.0, -1.0, 0.0));
+  }
+}
@@ -0,0 +1,49 @@
+//! A field whose density is displaced by adding another field's, e.g. noise.
+
+use cgmath::{Point3, Vector3};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field, Displacement> {
+  pub field: Field,
+  pub displacement: Displacement,
+}
+
+impl<Field, Displacement> field::T for T<Field, Displacement> where
+  Field: field::T,
+  Displacement: field::T,
+{
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field, p) + field::T::density(&self.displacement, p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::numerical_normal(self, p, field::NORMAL_STEP)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (min1, max1) = field::T::density_bounds(&self.field, low, high)?;
+    let (min2, max2) = field::T::density_bounds(&self.displacement, low, high)?;
+    Some((min1 + min2, max1 + max2))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn displacements() {
+    // Raise the ground by one everywhere.
+    let ground = field::half_space::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    let raised = super::T { field: ground, displacement: field::half_space::T { normal: Vector3::new(0.0, 0.0, 0.0), offset: 1.0 } };
+    assert_eq!(raised.density(&Point3::new(4.0, 1.0, -3.0)), 0.0);
+    assert!((raised.normal(&Point3::new(4.0, 1.0, -3.0)) - Vector3::new(0.0, 1.0, 0.0)).magnitude2() < 1e-6);
+    assert_eq!(raised.density_bounds(&Point3::new(0.0, 0.0, 0.0), &Point3::new(1.0, 2.0, 1.0)), Some((-1.0, 1.0)));
+  }
+}
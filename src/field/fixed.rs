@@ -0,0 +1,354 @@
+//! Deterministic fixed-point fields, for when every machine has to produce identical voxels.
+//!
+//! Fields here are evaluated entirely in integer arithmetic. `Deterministic` adapts them to
+//! `field::T`: points are rounded to fixed-point and the results converted back exactly, so the
+//! same inputs always give bit-identical outputs. The rest of brushing, e.g.
+//! `surface_vertex::of_field`, only uses correctly-rounded `f32` operations on those outputs,
+//! which Rust never fuses or reorders, so whole brushes are deterministic too.
+//!
+//! Only the fields in this module are deterministic; a brush using any other field isn't.
+//! `command::Field::Deterministic` describes them for logs that have to replay identically.
+
+use cgmath::{Point3, Vector3};
+use std::cmp;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+use field;
+
+/// The number of fractional bits in a fixed-point number.
+pub const FRAC_BITS: u32 = 16;
+
+/// A fixed-point number with `FRAC_BITS` fractional bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct T(pub i64);
+
+#[allow(missing_docs)]
+pub type Point = [T; 3];
+
+impl T {
+  #[allow(missing_docs)]
+  pub fn of_int(x: i64) -> T {
+    T(x << FRAC_BITS)
+  }
+
+  /// The nearest fixed-point number to `x`.
+  pub fn of_f32(x: f32) -> T {
+    // Scaling by a power of two is exact, and rounding is deterministic.
+    T((x * (1 << FRAC_BITS) as f32).round() as i64)
+  }
+
+  /// The nearest `f32` to this number.
+  pub fn to_f32(self) -> f32 {
+    self.0 as f32 / (1 << FRAC_BITS) as f32
+  }
+
+  /// The square root, rounded down. Negative numbers have a square root of zero.
+  pub fn sqrt(self) -> T {
+    if self.0 <= 0 {
+      return T(0)
+    }
+    // sqrt(x / 2^k) * 2^k = sqrt(x * 2^k)
+    let x = (self.0 as u128) << FRAC_BITS;
+    let mut root = 0u128;
+    let mut bit = 1u128 << 126;
+    while bit > x {
+      bit >>= 2;
+    }
+    let mut x = x;
+    while bit != 0 {
+      if x >= root + bit {
+        x -= root + bit;
+        root = (root >> 1) + bit;
+      } else {
+        root >>= 1;
+      }
+      bit >>= 2;
+    }
+    T(root as i64)
+  }
+}
+
+// Results too large for a `T` saturate, rather than wrapping or panicking.
+impl Add for T {
+  type Output = T;
+  fn add(self, rhs: T) -> T {
+    T(self.0.saturating_add(rhs.0))
+  }
+}
+
+impl Sub for T {
+  type Output = T;
+  fn sub(self, rhs: T) -> T {
+    T(self.0.saturating_sub(rhs.0))
+  }
+}
+
+fn saturate(x: i128) -> T {
+  T(cmp::max(i64::MIN as i128, cmp::min(i64::MAX as i128, x)) as i64)
+}
+
+impl Mul for T {
+  type Output = T;
+  fn mul(self, rhs: T) -> T {
+    saturate((self.0 as i128 * rhs.0 as i128) >> FRAC_BITS)
+  }
+}
+
+/// Dividing by zero saturates in the direction of the numerator, and `0 / 0` is zero.
+impl Div for T {
+  type Output = T;
+  fn div(self, rhs: T) -> T {
+    if rhs.0 == 0 {
+      return T(self.0.signum() * i64::MAX)
+    }
+    saturate(((self.0 as i128) << FRAC_BITS) / rhs.0 as i128)
+  }
+}
+
+impl Neg for T {
+  type Output = T;
+  fn neg(self) -> T {
+    T(self.0.saturating_neg())
+  }
+}
+
+fn dot(a: &Point, b: &Point) -> T {
+  a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+fn normalize(v: &Point) -> Point {
+  let length = dot(v, v).sqrt();
+  if length == T(0) {
+    return *v
+  }
+  [v[0] / length, v[1] / length, v[2] / length]
+}
+
+/// A field evaluated in fixed-point.
+pub trait Field: Send + Sync {
+  /// The density of the material at this point.
+  fn density(&self, p: &Point) -> T;
+
+  /// The surface normal at a given point.
+  fn normal(&self, p: &Point) -> Point;
+}
+
+impl<F: ?Sized> Field for Box<F> where F: Field {
+  fn density(&self, p: &Point) -> T {
+    (**self).density(p)
+  }
+
+  fn normal(&self, p: &Point) -> Point {
+    (**self).normal(p)
+  }
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+  pub radius: T,
+}
+
+impl Field for Sphere {
+  fn density(&self, p: &Point) -> T {
+    self.radius*self.radius - dot(p, p)
+  }
+
+  fn normal(&self, p: &Point) -> Point {
+    normalize(p)
+  }
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy)]
+pub struct Translation<Field> {
+  pub translation: Point,
+  pub field: Field,
+}
+
+impl<F> Field for Translation<F> where F: Field {
+  fn density(&self, p: &Point) -> T {
+    let t = &self.translation;
+    self.field.density(&[p[0] - t[0], p[1] - t[1], p[2] - t[2]])
+  }
+
+  fn normal(&self, p: &Point) -> Point {
+    let t = &self.translation;
+    self.field.normal(&[p[0] - t[0], p[1] - t[1], p[2] - t[2]])
+  }
+}
+
+/// A field rotated by an orthonormal matrix, given as rows.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy)]
+pub struct Rotation<Field> {
+  pub rows: [Point; 3],
+  pub field: Field,
+}
+
+impl<F> Field for Rotation<F> where F: Field {
+  fn density(&self, p: &Point) -> T {
+    let r = &self.rows;
+    // The inverse of an orthonormal matrix is its transpose.
+    let column = |i: usize| [r[0][i], r[1][i], r[2][i]];
+    self.field.density(&[dot(&column(0), p), dot(&column(1), p), dot(&column(2), p)])
+  }
+
+  fn normal(&self, p: &Point) -> Point {
+    let r = &self.rows;
+    let column = |i: usize| [r[0][i], r[1][i], r[2][i]];
+    let n = self.field.normal(&[dot(&column(0), p), dot(&column(1), p), dot(&column(2), p)]);
+    [dot(&r[0], &n), dot(&r[1], &n), dot(&r[2], &n)]
+  }
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy)]
+pub struct Intersection<Field1, Field2>(pub Field1, pub Field2);
+
+impl<F1, F2> Field for Intersection<F1, F2> where F1: Field, F2: Field {
+  fn density(&self, p: &Point) -> T {
+    cmp::min(self.0.density(p), self.1.density(p))
+  }
+
+  fn normal(&self, p: &Point) -> Point {
+    if self.0.density(p) < self.1.density(p) {
+      self.0.normal(p)
+    } else {
+      self.1.normal(p)
+    }
+  }
+}
+
+/// Use a fixed-point field as a `field::T`.
+#[derive(Debug, Clone, Copy)]
+pub struct Deterministic<Field>(pub Field);
+
+fn quantize(p: &Point3<f32>) -> Point {
+  [T::of_f32(p.x), T::of_f32(p.y), T::of_f32(p.z)]
+}
+
+impl<F> field::T for Deterministic<F> where F: Field {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.0.density(&quantize(p)).to_f32()
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let n = self.0.normal(&quantize(p));
+    Vector3::new(n[0].to_f32(), n[1].to_f32(), n[2].to_f32())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::Point3;
+
+  use brush;
+  use command;
+  use impls::surface_vertex;
+  use mosaic;
+  use tree;
+  use tree::save;
+
+  use super::*;
+
+  // FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+  fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+  }
+
+  #[test]
+  fn arithmetic() {
+    assert_eq!(T::of_f32(1.5) * T::of_f32(-2.25), T::of_f32(-3.375));
+    assert_eq!(T::of_int(3) / T::of_int(4), T::of_f32(0.75));
+    assert_eq!(T::of_int(9).sqrt(), T::of_int(3));
+    assert_eq!(T::of_f32(2.0).sqrt(), T(92681));
+    assert_eq!(T::of_f32(-0.75).to_f32(), -0.75);
+
+    assert_eq!(T::of_int(1) / T(0), T(i64::MAX));
+    assert_eq!(T::of_int(-1) / T(0), T(-i64::MAX));
+    assert_eq!(T(0) / T(0), T(0));
+    assert_eq!(T(i64::MAX) * T::of_int(2), T(i64::MAX));
+    assert_eq!(T(i64::MAX) / T(1), T(i64::MAX));
+    assert_eq!(T(i64::MAX) + T(1), T(i64::MAX));
+    assert_eq!(T(i64::MIN) - T(1), T(i64::MIN));
+  }
+
+  #[test]
+  fn far_away_points() {
+    let sphere = Sphere { radius: T::of_int(10) };
+    let far = [T::of_f32(1e7), T::of_f32(-1e7), T::of_f32(1e7)];
+    assert!(sphere.density(&far) < T(0));
+    let normal = sphere.normal(&far);
+    assert!(normal[0] > T(0) && normal[1] < T(0) && normal[2] > T(0));
+  }
+
+  fn field() -> Translation<Rotation<Intersection<Sphere, Sphere>>> {
+    let half = T::of_f32(0.5);
+    let root3 = T::of_f32(0.8660254);
+    Translation {
+      translation: [T::of_f32(0.3), T::of_f32(-0.7), T::of_f32(1.1)],
+      field: Rotation {
+        rows: [[half, -root3, T(0)], [root3, half, T(0)], [T(0), T(0), T::of_int(1)]],
+        field: Intersection(Sphere { radius: T::of_f32(3.5) }, Sphere { radius: T::of_int(5) }),
+      },
+    }
+  }
+
+  fn generate(b: &::bounds::T) -> Option<surface_vertex::T<u8>> {
+    if b.lg_size == 0 { Some(surface_vertex::T::Volume(0)) } else { None }
+  }
+
+  fn hash_tree(tree: &tree::T<surface_vertex::T<u8>>) -> u64 {
+    let mut bytes = Vec::new();
+    save::write(&mut bytes, tree).unwrap();
+    hash(&bytes)
+  }
+
+  // Any change to this hash means trees brushed on different versions or machines could differ.
+  const GOLDEN: u64 = 18330712880313105336;
+
+  #[test]
+  fn brushes_are_bit_identical() {
+    let mosaic = mosaic::solid::T { field: Deterministic(field()), material: 1u8 };
+    let bounds = brush::Bounds::new(Point3::new(-5, -5, -5), Point3::new(5, 5, 5));
+
+    let mut tree = tree::new();
+    tree.grow_to_hold(&::bounds::new(0, 0, 0, 3));
+    tree.brush(&brush::new(bounds, mosaic, 0), &brush::Mode::Add, &mut generate, &mut |_, _| {});
+    assert_eq!(hash_tree(&tree), GOLDEN);
+  }
+
+  #[test]
+  fn commands_are_bit_identical() {
+    let f = field();
+    let description =
+      command::Fixed::Translation {
+        translation: f.translation,
+        field: Box::new(command::Fixed::Rotation {
+          rows: f.field.rows,
+          field: Box::new(command::Fixed::Intersection(
+            Box::new(command::Fixed::Sphere { radius: T::of_f32(3.5) }),
+            Box::new(command::Fixed::Sphere { radius: T::of_int(5) }),
+          )),
+        }),
+      };
+    let edit =
+      command::T {
+        brush: command::Brush {
+          low: [-5, -5, -5],
+          high: [5, 5, 5],
+          mosaic: command::Mosaic::Solid { field: command::Field::Deterministic(description), material: 1u8 },
+          min_lg_size: 0,
+          strength: 1.0,
+          falloff: None,
+        },
+        mode: brush::Mode::Add,
+      };
+
+    let mut tree = tree::new();
+    tree.grow_to_hold(&::bounds::new(0, 0, 0, 3));
+    edit.apply(&mut tree, &mut generate, &mut |_, _| {}).unwrap();
+    assert_eq!(hash_tree(&tree), GOLDEN);
+  }
+}
@@ -0,0 +1,199 @@
+//! Fractal noise: several octaves of `perlin` noise at increasing frequencies, added together.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+
+use field;
+use field::perlin;
+
+/// How the octaves are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+  /// Fractional Brownian motion: the octaves are simply added.
+  Fbm,
+  /// The absolute values of the octaves are added, giving billowy shapes with creases.
+  Turbulence,
+  /// A ridged multifractal: each octave is folded into sharp ridges, and weighted by the octave
+  /// before it, so valleys stay smooth while ridges get more detailed.
+  Ridged,
+}
+
+/// The most octaves a fractal evaluates. Past this, octaves are too fine to matter.
+pub const MAX_OCTAVES: u32 = 16;
+
+#[derive(Debug, Clone)]
+#[allow(missing_docs)]
+pub struct T {
+  /// The first octave. Its frequency and amplitude are those of the whole fractal.
+  pub noise: perlin::T,
+  pub kind: Kind,
+  /// The number of octaves, up to `MAX_OCTAVES`.
+  pub octaves: u32,
+  /// How much the frequency is multiplied by for each octave.
+  pub lacunarity: f32,
+  /// How much the amplitude is multiplied by for each octave.
+  pub gain: f32,
+}
+
+/// Fractal noise with the usual lacunarity of 2 and gain of 1/2.
+pub fn new(noise: perlin::T, kind: Kind, octaves: u32) -> T {
+  T {
+    noise: noise,
+    kind: kind,
+    octaves: octaves,
+    lacunarity: 2.0,
+    gain: 0.5,
+  }
+}
+
+impl T {
+  fn octave_count(&self) -> u32 {
+    self.octaves.min(MAX_OCTAVES)
+  }
+
+  // Call `f` with each octave's noise and gradient, and its amplitude.
+  fn each_octave<F>(&self, p: &Point3<f32>, mut f: F) where F: FnMut(f32, Vector3<f32>, f32) {
+    let mut frequency = self.noise.frequency;
+    let mut amplitude = self.noise.amplitude;
+    for _ in 0..self.octave_count() {
+      let (value, gradient) = self.noise.sample_unscaled(&(p * frequency));
+      f(value, gradient * frequency, amplitude);
+      frequency *= self.lacunarity;
+      amplitude *= self.gain;
+    }
+  }
+
+  /// The noise at a point, and its gradient if it's known analytically.
+  pub fn sample(&self, p: &Point3<f32>) -> (f32, Option<Vector3<f32>>) {
+    let mut total = 0.0;
+    let mut total_gradient = Vector3::new(0.0, 0.0, 0.0);
+    match self.kind {
+      Kind::Fbm => {
+        self.each_octave(p, |value, gradient, amplitude| {
+          total += amplitude * value;
+          total_gradient += gradient * amplitude;
+        });
+        (total, Some(total_gradient))
+      },
+      Kind::Turbulence => {
+        self.each_octave(p, |value, gradient, amplitude| {
+          total += amplitude * value.abs();
+          total_gradient += gradient * (amplitude * value.signum());
+        });
+        (total, Some(total_gradient))
+      },
+      Kind::Ridged => {
+        let mut weight = 1.0;
+        self.each_octave(p, |value, _, amplitude| {
+          let ridge = 1.0 - value.abs();
+          let signal = ridge * ridge * weight;
+          total += amplitude * signal;
+          weight = signal.clamp(0.0, 1.0);
+        });
+        (total, None)
+      },
+    }
+  }
+
+  // The total amplitude of all the octaves.
+  fn total_amplitude(&self) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = self.noise.amplitude.abs();
+    for _ in 0..self.octave_count() {
+      total += amplitude;
+      amplitude *= self.gain.abs();
+    }
+    total
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.sample(p).0
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    match self.sample(p).1 {
+      None => {
+        // Take a step small relative to the finest octave.
+        let finest = self.noise.frequency * self.lacunarity.powi(self.octave_count().max(1) as i32 - 1);
+        field::numerical_normal(self, p, field::NORMAL_STEP / finest)
+      },
+      Some(gradient) => {
+        if gradient.magnitude2() > 0.0 { -gradient.normalize() } else { gradient }
+      },
+    }
+  }
+
+  fn density_bounds(&self, _: &Point3<f32>, _: &Point3<f32>) -> Option<(f32, f32)> {
+    let max = self.total_amplitude() * perlin::MAX_MAGNITUDE;
+    match self.kind {
+      Kind::Fbm => Some((-max, max)),
+      Kind::Turbulence => Some((0.0, max)),
+      Kind::Ridged => {
+        // Each octave adds `ridge * ridge * weight`, with `ridge` in [1 - MAX_MAGNITUDE, 1] and
+        // `weight` in [0, 1].
+        let max = self.total_amplitude() * (perlin::MAX_MAGNITUDE - 1.0).powi(2);
+        if self.noise.amplitude >= 0.0 && self.gain >= 0.0 { Some((0.0, max)) } else { Some((-max, max)) }
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use brush;
+  use field;
+  use field::perlin;
+  use field::T;
+  use impls::surface_vertex;
+  use mosaic;
+  use tree;
+
+  #[test]
+  fn fractal_terrain() {
+    let mut noise = perlin::new(3);
+    noise.frequency = 0.125;
+    noise.amplitude = 2.0;
+    for &kind in &[super::Kind::Fbm, super::Kind::Turbulence, super::Kind::Ridged] {
+      let fractal = super::new(noise.clone(), kind, 4);
+      let (min, max) = fractal.density_bounds(&Point3::new(0.0, 0.0, 0.0), &Point3::new(1.0, 1.0, 1.0)).unwrap();
+      assert!(min.is_finite() && max.is_finite());
+      // Avoid the origin, where turbulence has a crease.
+      for i in 1..20 {
+        let p = Point3::new(i as f32 * 1.37, i as f32 * -0.61, i as f32 * 0.83);
+        let d = fractal.density(&p);
+        assert!(min <= d && d <= max, "{:?} {:?}", kind, p);
+        if fractal.sample(&p).1.is_some() {
+          let numerical = field::numerical_normal(&fractal, &p, 1.0 / 256.0);
+          assert!((fractal.normal(&p) - numerical).magnitude2() < 1e-2, "{:?} {:?}", kind, p);
+        }
+      }
+    }
+
+    // Octaves past the limit are ignored.
+    let p = Point3::new(1.37, -0.61, 0.83);
+    let many = super::new(noise.clone(), super::Kind::Fbm, 1000);
+    assert_eq!(many.density(&p), super::new(noise.clone(), super::Kind::Fbm, super::MAX_OCTAVES).density(&p));
+
+    // Rough ground, brushed into a tree.
+    let ground = field::half_space::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    let terrain =
+      mosaic::solid::T {
+        field: field::displace::T { field: ground, displacement: super::new(noise, super::Kind::Fbm, 4) },
+        material: 1,
+      };
+    let bounds = brush::Bounds::new(Point3::new(-8, -4, -8), Point3::new(8, 4, 8));
+    let mut tree = tree::new();
+    tree.grow_to_hold(&::bounds::new(0, 0, 0, 4));
+    let mut surface = 0;
+    tree.brush(
+      &brush::new(bounds, terrain, 0),
+      &brush::Mode::Add,
+      &mut |b| if b.lg_size == 0 { Some(surface_vertex::T::Volume(0)) } else { None },
+      &mut |voxel, _| if let surface_vertex::T::Surface(_) = *voxel { surface += 1 },
+    );
+    assert!(surface > 0);
+  }
+}
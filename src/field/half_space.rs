@@ -0,0 +1,63 @@
+//! A field filling everything on one side of a plane.
+
+use cgmath::{Point3, Vector3, EuclideanSpace, InnerSpace};
+
+use field;
+use field::plane;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  /// The unit normal of the boundary, pointing out of the filled side.
+  pub normal: Vector3<f32>,
+  /// The boundary contains the points `p` with `p.dot(normal) == offset`.
+  pub offset: f32,
+}
+
+/// Everything behind the plane through `point` with the given normal.
+pub fn new(point: Point3<f32>, normal: Vector3<f32>) -> T {
+  let normal = normal.normalize();
+  T {
+    normal: normal,
+    offset: point.to_vec().dot(normal),
+  }
+}
+
+/// A half-space filled with a single material.
+pub fn solid<Material>(point: Point3<f32>, normal: Vector3<f32>, material: Material) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(point, normal),
+    material: material,
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.offset - p.to_vec().dot(self.normal)
+  }
+
+  fn normal(&self, _: &Point3<f32>) -> Vector3<f32> {
+    self.normal
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (min, max) = plane::height_bounds(&self.normal, self.offset, low, high);
+    Some((-max, -min))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field::T;
+
+  #[test]
+  fn half_spaces() {
+    let ground = super::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(ground.density(&Point3::new(5.0, -1.0, 2.0)), 2.0);
+    assert_eq!(ground.normal(&Point3::new(5.0, -1.0, 2.0)), Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(ground.density_bounds(&Point3::new(0.0, 2.0, 0.0), &Point3::new(1.0, 3.0, 1.0)), Some((-2.0, -1.0)));
+  }
+}
@@ -5,9 +5,7 @@ use cgmath::{Point3, Vector3};
 use field;
 
 #[allow(missing_docs)]
-pub struct T ([Box<field::T>; 2]);
-
-unsafe impl Send for T {}
+pub struct T ([Box<dyn field::T>; 2]);
 
 #[allow(missing_docs)]
 pub fn new<Field1, Field2>(field1: Field1, field2: Field2) -> T
@@ -19,11 +17,11 @@ pub fn new<Field1, Field2>(field1: Field1, field2: Field2) -> T
 }
 
 impl field::T for T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     f32::min(self.0[0].density(p), self.0[1].density(p))
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
     let d1 = self.0[0].density(p);
     let d2 = self.0[1].density(p);
     if d1 < d2 {
@@ -32,4 +30,10 @@ impl field::T for T {
       self.0[1].normal(p)
     }
   }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (min1, max1) = self.0[0].density_bounds(low, high)?;
+    let (min2, max2) = self.0[1].density_bounds(low, high)?;
+    Some((f32::min(min1, min2), f32::min(max1, max2)))
+  }
 }
@@ -0,0 +1,74 @@
+//! A field defined by mirroring one side of another field across the planes through the origin.
+
+use cgmath::{Point3, Vector3};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  /// Which axes to mirror along. Along each of these, the negative side becomes a copy of the
+  /// positive side.
+  pub axes: [bool; 3],
+  pub field: Field,
+}
+
+impl<Field> T<Field> {
+  fn fold(&self, x: f32, axis: usize) -> f32 {
+    if self.axes[axis] { x.abs() } else { x }
+  }
+
+  fn flip(&self, n: f32, x: f32, axis: usize) -> f32 {
+    if self.axes[axis] && x < 0.0 { -n } else { n }
+  }
+
+  fn fold_range(&self, low: f32, high: f32, axis: usize) -> (f32, f32) {
+    if !self.axes[axis] || low >= 0.0 {
+      (low, high)
+    } else if high <= 0.0 {
+      (-high, -low)
+    } else {
+      (0.0, f32::max(-low, high))
+    }
+  }
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    let p = Point3::new(self.fold(p.x, 0), self.fold(p.y, 1), self.fold(p.z, 2));
+    field::T::density(&self.field, &p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let folded = Point3::new(self.fold(p.x, 0), self.fold(p.y, 1), self.fold(p.z, 2));
+    let n = field::T::normal(&self.field, &folded);
+    Vector3::new(self.flip(n.x, p.x, 0), self.flip(n.y, p.y, 1), self.flip(n.z, p.z, 2))
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (lx, hx) = self.fold_range(low.x, high.x, 0);
+    let (ly, hy) = self.fold_range(low.y, high.y, 1);
+    let (lz, hz) = self.fold_range(low.z, high.z, 2);
+    field::T::density_bounds(&self.field, &Point3::new(lx, ly, lz), &Point3::new(hx, hy, hz))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn mirrors() {
+    let ball = field::translation::T { translation: Vector3::new(2.0, 0.0, 0.0), field: field::sphere::T { radius: 1.0 } };
+    let pair = super::T { axes: [true, false, false], field: ball };
+    assert_eq!(pair.density(&Point3::new(-2.0, 0.0, 0.0)), 1.0);
+    assert_eq!(pair.normal(&Point3::new(-3.0, 0.0, 0.0)), Vector3::new(-1.0, 0.0, 0.0));
+    assert_eq!(
+      pair.density_bounds(&Point3::new(-3.0, -1.0, -1.0), &Point3::new(-1.0, 1.0, 1.0)),
+      ball.density_bounds(&Point3::new(1.0, -1.0, -1.0), &Point3::new(3.0, 1.0, 1.0)),
+    );
+  }
+}
@@ -1,28 +1,161 @@
 //! A density field defining a density and normal everywhere.
+//!
+//! Fields are evaluated through `&self` and are `Sync`, so one field can be shared by several
+//! threads. Use `cache` to memoize an expensive field.
 
-use cgmath::{Point3, Vector3};
-use std::ops::DerefMut;
+use cgmath::{Point3, Vector3, EuclideanSpace, InnerSpace};
+use std::f32;
+use std::ops::Deref;
 
+pub mod affine;
+pub mod bend;
+pub mod cache;
+pub mod capsule;
+pub mod complement;
+pub mod cone;
+pub mod cuboid;
+pub mod cylinder;
+pub mod difference;
+pub mod displace;
+pub mod fixed;
+pub mod fractal;
+pub mod half_space;
+pub mod mirror;
+pub mod perlin;
+pub mod plane;
+pub mod repeat;
+pub mod scale;
 pub mod sphere;
+pub mod torus;
+pub mod twist;
+pub mod union;
 pub mod intersection;
 pub mod rotation;
 pub mod translation;
 
 #[allow(missing_docs)]
-pub trait T {
+pub trait T: Send + Sync {
   /// The density of the material at this point.
-  fn density(&mut self, p: &Point3<f32>) -> f32;
+  fn density(&self, p: &Point3<f32>) -> f32;
 
   /// The surface normal at a given point.
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32>;
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32>;
+
+  /// Lower and upper bounds on the density anywhere in the box from `low` to `high`,
+  /// or `None` if they aren't known.
+  fn density_bounds(&self, _low: &Point3<f32>, _high: &Point3<f32>) -> Option<(f32, f32)> {
+    None
+  }
+}
+
+/// The box containing every corner of the box from `low` to `high` after `f` is applied.
+pub fn transform_box<F>(low: &Point3<f32>, high: &Point3<f32>, f: F) -> (Point3<f32>, Point3<f32>)
+  where F: Fn(Point3<f32>) -> Point3<f32>
+{
+  let mut min = Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+  let mut max = Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+  for &x in &[low.x, high.x] {
+  for &y in &[low.y, high.y] {
+  for &z in &[low.z, high.z] {
+    let p = f(Point3::new(x, y, z));
+    min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+    max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+  }}}
+  (min, max)
+}
+
+/// Bounds on the density in the box from `low` to `high`, for a field whose density changes no
+/// faster than the position does, e.g. a signed distance.
+pub fn distance_bounds<Field>(field: &Field, low: &Point3<f32>, high: &Point3<f32>) -> (f32, f32)
+  where Field: T + ?Sized
+{
+  let d = field.density(&low.midpoint(*high));
+  let r = (high - low).magnitude() / 2.0;
+  (d - r, d + r)
+}
+
+/// The distance of `p` from the y axis.
+pub fn radial_distance(p: &Point3<f32>) -> f32 {
+  (p.x*p.x + p.z*p.z).sqrt()
+}
+
+/// The normal at `p` of a field that's symmetric around the y axis, given its normal in the
+/// plane through the axis and `p`: `radial` away from the axis and `y` along it. On the axis, where
+/// every direction is away from it, `radial` points along +x.
+pub fn revolved_normal(p: &Point3<f32>, radial: f32, y: f32) -> Vector3<f32> {
+  let r = radial_distance(p);
+  if r == 0.0 {
+    return Vector3::new(radial, y, 0.0)
+  }
+  Vector3::new(radial * p.x / r, y, radial * p.z / r)
+}
+
+/// The step used by `numerical_normal` when fields don't choose their own.
+pub const NORMAL_STEP: f32 = 1.0 / 64.0;
+
+/// Estimate the normal at `p` by central differences of the density, `step` apart. This works for
+/// any field, but costs six density evaluations.
+pub fn numerical_normal<Field>(field: &Field, p: &Point3<f32>, step: f32) -> Vector3<f32>
+  where Field: T + ?Sized
+{
+  let difference = |d: Vector3<f32>| field.density(&(p + -d)) - field.density(&(p + d));
+  let gradient =
+    Vector3::new(
+      difference(Vector3::new(step, 0.0, 0.0)),
+      difference(Vector3::new(0.0, step, 0.0)),
+      difference(Vector3::new(0.0, 0.0, step)),
+    );
+  if gradient.magnitude2() > 0.0 { gradient.normalize() } else { gradient }
+}
+
+/// A density and the normal there.
+pub type Sample = (f32, Vector3<f32>);
+
+/// Combine two samples like `max`, but smoothly: where the densities are within `blend` of each
+/// other, the result rises above both and the normals are blended to match. A `blend` of zero
+/// is exactly `max`.
+pub fn smooth_max(a: Sample, b: Sample, blend: f32) -> Sample {
+  if blend <= 0.0 || (a.0 - b.0).abs() >= blend {
+    return if a.0 >= b.0 { a } else { b }
+  }
+  let h = 0.5 + 0.5 * (a.0 - b.0) / blend;
+  let density = b.0 + (a.0 - b.0) * h + blend * h * (1.0 - h);
+  // This is exactly the direction of the gradient of `density`.
+  let normal = b.1 * (1.0 - h) + a.1 * h;
+  let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { a.1 };
+  (density, normal)
+}
+
+/// Combine two samples like `min`, smoothly; see `smooth_max`.
+pub fn smooth_min(a: Sample, b: Sample, blend: f32) -> Sample {
+  let (density, normal) = smooth_max((-a.0, -a.1), (-b.0, -b.1), blend);
+  (-density, -normal)
 }
 
 impl<X: ?Sized> T for Box<X> where X: T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
-    T::density(self.deref_mut(), p)
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    T::density(self.deref(), p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    T::normal(self.deref(), p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    T::density_bounds(self.deref(), low, high)
+  }
+}
+
+impl<X: ?Sized> T for &X where X: T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    T::density(*self, p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    T::normal(*self, p)
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
-    T::normal(self.deref_mut(), p)
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    T::density_bounds(*self, low, high)
   }
 }
@@ -0,0 +1,163 @@
+//! Seedable Perlin gradient noise, as a field.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+
+use field;
+
+// The gradients at lattice points: the midpoints of a cube's edges, padded to 16 as in
+// Perlin's improved noise so a hash can pick one with a mask.
+const GRADIENTS: [[f32; 3]; 16] = [
+  [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+  [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+  [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+  [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [0.0, -1.0, 1.0], [0.0, -1.0, -1.0],
+];
+
+/// An upper bound on the magnitude of unscaled noise: no gradient is longer than `sqrt(2)`, and
+/// no point is further than `sqrt(3)` from a lattice point.
+pub const MAX_MAGNITUDE: f32 = 2.4494898;
+
+#[derive(Debug, Clone)]
+#[allow(missing_docs)]
+pub struct T {
+  permutation: [u8; 256],
+  /// How many lattice cells there are per unit.
+  pub frequency: f32,
+  /// What the noise is multiplied by.
+  pub amplitude: f32,
+}
+
+/// Noise with a frequency and amplitude of one. The same seed always gives the same noise.
+pub fn new(seed: u32) -> T {
+  let mut permutation = [0; 256];
+  for (i, p) in permutation.iter_mut().enumerate() {
+    *p = i as u8;
+  }
+  // Shuffle with a xorshift generator, which behaves the same everywhere.
+  let mut state = seed ^ 0x9e3779b9;
+  if state == 0 {
+    state = 1;
+  }
+  for i in (1..256).rev() {
+    state ^= state << 13;
+    state ^= state >> 17;
+    state ^= state << 5;
+    permutation.swap(i, state as usize % (i + 1));
+  }
+  T {
+    permutation: permutation,
+    frequency: 1.0,
+    amplitude: 1.0,
+  }
+}
+
+fn fade(t: f32) -> f32 {
+  t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn fade_derivative(t: f32) -> f32 {
+  30.0 * t * t * (t * (t - 2.0) + 1.0)
+}
+
+impl T {
+  fn gradient(&self, x: i32, y: i32, z: i32) -> Vector3<f32> {
+    let p = |i: i32| self.permutation[(i & 255) as usize] as i32;
+    let g = GRADIENTS[(p(p(p(x) + y) + z) & 15) as usize];
+    Vector3::new(g[0], g[1], g[2])
+  }
+
+  /// The unscaled noise at a point, and its gradient.
+  pub fn sample_unscaled(&self, p: &Point3<f32>) -> (f32, Vector3<f32>) {
+    let (x, y, z) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let f = Vector3::new(p.x - x, p.y - y, p.z - z);
+    let (x, y, z) = (x as i32, y as i32, z as i32);
+    let u = Vector3::new(fade(f.x), fade(f.y), fade(f.z));
+    let du = Vector3::new(fade_derivative(f.x), fade_derivative(f.y), fade_derivative(f.z));
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+      let g = self.gradient(x + dx, y + dy, z + dz);
+      (g.dot(f - Vector3::new(dx as f32, dy as f32, dz as f32)), g)
+    };
+    let (va, ga) = corner(0, 0, 0);
+    let (vb, gb) = corner(1, 0, 0);
+    let (vc, gc) = corner(0, 1, 0);
+    let (vd, gd) = corner(1, 1, 0);
+    let (ve, ge) = corner(0, 0, 1);
+    let (vf, gf) = corner(1, 0, 1);
+    let (vg, gg) = corner(0, 1, 1);
+    let (vh, gh) = corner(1, 1, 1);
+
+    // Trilinear interpolation with faded weights, expanded so it can be differentiated.
+    let k1 = vb - va;
+    let k2 = vc - va;
+    let k3 = ve - va;
+    let k4 = va - vb - vc + vd;
+    let k5 = va - vc - ve + vg;
+    let k6 = va - vb - ve + vf;
+    let k7 = -va + vb + vc - vd + ve - vf - vg + vh;
+    let value =
+      va + u.x*k1 + u.y*k2 + u.z*k3 + u.x*u.y*k4 + u.y*u.z*k5 + u.z*u.x*k6 + u.x*u.y*u.z*k7;
+
+    let interpolated_gradient =
+      ga + (gb - ga)*u.x + (gc - ga)*u.y + (ge - ga)*u.z +
+      (ga - gb - gc + gd)*(u.x*u.y) + (ga - gc - ge + gg)*(u.y*u.z) + (ga - gb - ge + gf)*(u.z*u.x) +
+      (-ga + gb + gc - gd + ge - gf - gg + gh)*(u.x*u.y*u.z);
+    let gradient =
+      interpolated_gradient +
+      Vector3::new(
+        du.x * (k1 + u.y*k4 + u.z*k6 + u.y*u.z*k7),
+        du.y * (k2 + u.x*k4 + u.z*k5 + u.x*u.z*k7),
+        du.z * (k3 + u.y*k5 + u.x*k6 + u.x*u.y*k7),
+      );
+    (value, gradient)
+  }
+
+  /// The noise at a point, and its gradient.
+  pub fn sample(&self, p: &Point3<f32>) -> (f32, Vector3<f32>) {
+    let (value, gradient) = self.sample_unscaled(&(p * self.frequency));
+    (self.amplitude * value, gradient * (self.amplitude * self.frequency))
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.sample(p).0
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let gradient = self.sample(p).1;
+    if gradient.magnitude2() > 0.0 { -gradient.normalize() } else { gradient }
+  }
+
+  fn density_bounds(&self, _: &Point3<f32>, _: &Point3<f32>) -> Option<(f32, f32)> {
+    let max = self.amplitude.abs() * MAX_MAGNITUDE;
+    Some((-max, max))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, InnerSpace};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn seeded_noise() {
+    let noise = super::new(7);
+    let p = Point3::new(1.3, -2.7, 0.4);
+    assert_eq!(noise.density(&Point3::new(3.0, -1.0, 2.0)), 0.0);
+    assert_eq!(noise.density(&p), super::new(7).density(&p));
+    assert!(noise.density(&p) != super::new(8).density(&p));
+
+    let scaled = super::T { frequency: 0.25, amplitude: 4.0, .. super::new(7) };
+    for i in 0..50 {
+      let p = Point3::new(i as f32 * 0.37, i as f32 * -0.61, i as f32 * 0.23);
+      let (min, max) = scaled.density_bounds(&p, &p).unwrap();
+      let d = scaled.density(&p);
+      assert!(min <= d && d <= max);
+      let numerical = field::numerical_normal(&scaled, &p, 1.0 / 256.0);
+      assert!((scaled.normal(&p) - numerical).magnitude2() < 1e-3, "{:?}", p);
+    }
+  }
+}
@@ -0,0 +1,86 @@
+//! A flat slab field centred on a plane, e.g. a floor or a wall.
+
+use cgmath::{Point3, Vector3, EuclideanSpace, InnerSpace};
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  /// The unit normal of the plane.
+  pub normal: Vector3<f32>,
+  /// The plane contains the points `p` with `p.dot(normal) == offset`.
+  pub offset: f32,
+  /// The thickness of the slab.
+  pub thickness: f32,
+}
+
+/// A slab centred on the plane through `point` with the given normal.
+pub fn new(point: Point3<f32>, normal: Vector3<f32>, thickness: f32) -> T {
+  let normal = normal.normalize();
+  T {
+    normal: normal,
+    offset: point.to_vec().dot(normal),
+    thickness: thickness,
+  }
+}
+
+/// A slab filled with a single material.
+pub fn solid<Material>(
+  point: Point3<f32>,
+  normal: Vector3<f32>,
+  thickness: f32,
+  material: Material,
+) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(point, normal, thickness),
+    material: material,
+  }
+}
+
+/// The range of `p.dot(normal) - offset` over the box from `low` to `high`.
+pub fn height_bounds(normal: &Vector3<f32>, offset: f32, low: &Point3<f32>, high: &Point3<f32>) -> (f32, f32) {
+  let range = |n: f32, l: f32, h: f32| if n >= 0.0 { (n*l, n*h) } else { (n*h, n*l) };
+  let (x0, x1) = range(normal.x, low.x, high.x);
+  let (y0, y1) = range(normal.y, low.y, high.y);
+  let (z0, z1) = range(normal.z, low.z, high.z);
+  (x0 + y0 + z0 - offset, x1 + y1 + z1 - offset)
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.thickness / 2.0 - (p.to_vec().dot(self.normal) - self.offset).abs()
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    if p.to_vec().dot(self.normal) < self.offset {
+      -self.normal
+    } else {
+      self.normal
+    }
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (min, max) = height_bounds(&self.normal, self.offset, low, high);
+    let nearest = if min > 0.0 { min } else if max < 0.0 { -max } else { 0.0 };
+    let farthest = f32::max(min.abs(), max.abs());
+    Some((self.thickness / 2.0 - farthest, self.thickness / 2.0 - nearest))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field::T;
+
+  #[test]
+  fn slabs() {
+    let floor = super::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 2.0, 0.0), 2.0);
+    assert_eq!(floor.density(&Point3::new(5.0, 1.5, -3.0)), 0.5);
+    assert_eq!(floor.density(&Point3::new(5.0, -1.0, -3.0)), -1.0);
+    assert_eq!(floor.normal(&Point3::new(0.0, 0.5, 0.0)), Vector3::new(0.0, -1.0, 0.0));
+    assert_eq!(floor.density_bounds(&Point3::new(0.0, 1.0, 0.0), &Point3::new(1.0, 3.0, 1.0)), Some((-1.0, 1.0)));
+  }
+}
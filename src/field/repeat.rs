@@ -0,0 +1,87 @@
+//! A field defined by repeating another field on a grid, forever or a fixed number of times.
+
+use cgmath::{Point3, Vector3};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  /// The distance between copies along each axis. Along axes where this is zero, the field
+  /// isn't repeated.
+  pub period: Vector3<f32>,
+  /// How many copies there are on each side of the original along each axis, or `None` to
+  /// repeat forever.
+  pub copies: Option<[u32; 3]>,
+  pub field: Field,
+}
+
+impl<Field> T<Field> {
+  fn cell(&self, x: f32, axis: usize) -> f32 {
+    let period = self.period[axis];
+    if period == 0.0 {
+      return 0.0
+    }
+    let cell = (x / period).round();
+    match self.copies {
+      None => cell,
+      Some(copies) => {
+        let copies = copies[axis] as f32;
+        cell.max(-copies).min(copies)
+      },
+    }
+  }
+
+  // The offset from a point to the corresponding point in the original field.
+  fn offset(&self, p: &Point3<f32>) -> Vector3<f32> {
+    Vector3::new(
+      -self.cell(p.x, 0) * self.period.x,
+      -self.cell(p.y, 1) * self.period.y,
+      -self.cell(p.z, 2) * self.period.z,
+    )
+  }
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field, &(p + self.offset(p)))
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::T::normal(&self.field, &(p + self.offset(p)))
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    // Only boxes within a single copy are handled.
+    let offset = self.offset(low);
+    if offset != self.offset(high) {
+      return None
+    }
+    field::T::density_bounds(&self.field, &(low + offset), &(high + offset))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn repeats() {
+    let post = field::cylinder::new(0.5, 2.0);
+    let fence = super::T { period: Vector3::new(3.0, 0.0, 0.0), copies: Some([2, 0, 0]), field: post };
+    for &x in &[-6.0, -3.0, 0.0, 3.0, 6.0] {
+      assert_eq!(fence.density(&Point3::new(x, 0.0, 0.0)), 0.5);
+    }
+    assert_eq!(fence.density(&Point3::new(9.0, 0.0, 0.0)), -2.5);
+    assert_eq!(fence.density(&Point3::new(1.5, 0.0, 0.0)), -1.0);
+    assert_eq!(fence.normal(&Point3::new(3.75, 0.0, 0.0)), Vector3::new(1.0, 0.0, 0.0));
+    assert_eq!(fence.density_bounds(&Point3::new(2.0, 0.0, 0.0), &Point3::new(4.0, 1.0, 1.0)), post.density_bounds(&Point3::new(-1.0, 0.0, 0.0), &Point3::new(1.0, 1.0, 1.0)));
+    assert_eq!(fence.density_bounds(&Point3::new(1.0, 0.0, 0.0), &Point3::new(2.0, 1.0, 1.0)), None);
+
+    let forever = super::T { copies: None, .. fence };
+    assert_eq!(forever.density(&Point3::new(300.0, 0.0, 0.0)), 0.5);
+  }
+}
@@ -12,13 +12,45 @@ pub struct T<Field> {
 }
 
 impl<Field> field::T for T<Field> where Field: field::T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let p = self.rotation.invert().rotate_point(*p);
-    field::T::density(&mut self.field, &p)
+    field::T::density(&self.field, &p)
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
     let p = self.rotation.invert().rotate_point(*p);
-    field::T::normal(&mut self.field, &p)
+    self.rotation.rotate_vector(field::T::normal(&self.field, &p))
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let rotation = self.rotation.invert();
+    let (low, high) = field::transform_box(low, high, |p| rotation.rotate_point(p));
+    field::T::density_bounds(&self.field, &low, &high)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, Basis3, Rad, Rotation, Rotation3, InnerSpace};
+  use std::f32;
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn normals_are_rotated() {
+    // A half-space facing +x, turned to face +y.
+    let wall = field::half_space::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+    let floor = super::T { rotation: Basis3::from_angle_z(Rad(f32::consts::FRAC_PI_2)), field: wall };
+    assert!(floor.density(&Point3::new(0.0, 0.5, 0.0)) > 0.0);
+    assert!(floor.density(&Point3::new(0.0, 1.5, 0.0)) < 0.0);
+    assert!((floor.normal(&Point3::new(0.0, 1.0, 0.0)) - Vector3::new(0.0, 1.0, 0.0)).magnitude2() < 1e-6);
+
+    // Around a skewed axis, the normal still follows the rotation, and still has unit length.
+    let rotation = Basis3::from_axis_angle(Vector3::new(1.0, 2.0, 3.0).normalize(), Rad(1.0));
+    let tilted = super::T { rotation: rotation, field: floor.field };
+    let n = tilted.normal(&Point3::new(0.0, 0.0, 0.0));
+    assert!((n - rotation.rotate_vector(Vector3::new(1.0, 0.0, 0.0))).magnitude2() < 1e-6);
+    assert!((n.magnitude() - 1.0).abs() < 1e-6);
   }
 }
@@ -0,0 +1,81 @@
+//! A field defined by scaling another field, possibly by a different amount along each axis.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  /// The scale along each axis. None of these should be zero.
+  pub scale: Vector3<f32>,
+  pub field: Field,
+}
+
+/// Scale a field by the same amount along every axis.
+pub fn uniform<Field>(scale: f32, field: Field) -> T<Field> {
+  T {
+    scale: Vector3::new(scale, scale, scale),
+    field: field,
+  }
+}
+
+impl<Field> T<Field> {
+  /// The point in the unscaled field corresponding to `p`.
+  pub fn unscale(&self, p: &Point3<f32>) -> Point3<f32> {
+    Point3::new(p.x / self.scale.x, p.y / self.scale.y, p.z / self.scale.z)
+  }
+
+  /// What the unscaled field's densities are multiplied by.
+  /// This is the smallest scale, so that e.g. a signed distance stays a lower bound on the
+  /// distance to the surface. For a uniform scale, it stays exact.
+  pub fn density_scale(&self) -> f32 {
+    self.scale.x.abs().min(self.scale.y.abs()).min(self.scale.z.abs())
+  }
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.density_scale() * field::T::density(&self.field, &self.unscale(p))
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    // Normals are transformed by the inverse transpose of the scale.
+    let n = field::T::normal(&self.field, &self.unscale(p));
+    Vector3::new(n.x / self.scale.x, n.y / self.scale.y, n.z / self.scale.z).normalize()
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let (low, high) = field::transform_box(low, high, |p| self.unscale(&p));
+    let (min, max) = field::T::density_bounds(&self.field, &low, &high)?;
+    let s = self.density_scale();
+    Some((s * min, s * max))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn ellipsoids() {
+    let ball = super::uniform(2.0, field::cuboid::new(Vector3::new(1.0, 1.0, 1.0)));
+    assert_eq!(ball.density(&Point3::new(0.0, 0.0, 0.0)), 2.0);
+    assert_eq!(ball.density(&Point3::new(3.0, 0.0, 0.0)), -1.0);
+
+    let ellipsoid = super::T { scale: Vector3::new(2.0, 1.0, 1.0), field: field::sphere::T { radius: 1.0 } };
+    assert_eq!(ellipsoid.density(&Point3::new(2.0, 0.0, 0.0)), 0.0);
+    assert!(ellipsoid.density(&Point3::new(0.0, 1.5, 0.0)) < 0.0);
+    // The normal is perpendicular to the surface, not just pointing away from the centre.
+    let p = Point3::new(2.0f32.sqrt(), 0.5f32.sqrt(), 0.0);
+    let expected = Vector3::new(1.0, 2.0, 0.0).normalize();
+    assert!((ellipsoid.normal(&p) - expected).magnitude2() < 1e-6);
+
+    let (min, max) = ellipsoid.density_bounds(&Point3::new(1.0, -0.5, -0.5), &Point3::new(3.0, 0.5, 0.5)).unwrap();
+    assert!(min <= ellipsoid.density(&Point3::new(3.0, 0.5, 0.5)));
+    assert!(max >= ellipsoid.density(&Point3::new(1.0, 0.0, 0.0)));
+  }
+}
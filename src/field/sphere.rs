@@ -10,14 +10,21 @@ pub struct T {
   pub radius: f32,
 }
 
-unsafe impl Send for T {}
-
 impl field::T for T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     self.radius*self.radius - p.to_vec().magnitude2()
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
     p.to_vec().normalize()
   }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    let nearest = |l: f32, h: f32| if l > 0.0 { l } else if h < 0.0 { h } else { 0.0 };
+    let farthest = |l: f32, h: f32| f32::max(l.abs(), h.abs());
+    let near = Vector3::new(nearest(low.x, high.x), nearest(low.y, high.y), nearest(low.z, high.z));
+    let far = Vector3::new(farthest(low.x, high.x), farthest(low.y, high.y), farthest(low.z, high.z));
+    let r2 = self.radius*self.radius;
+    Some((r2 - far.magnitude2(), r2 - near.magnitude2()))
+  }
 }
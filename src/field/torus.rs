@@ -0,0 +1,81 @@
+//! A torus field around the y axis.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+use cgmath::Vector2;
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T {
+  /// The distance from the y axis to the centre of the tube.
+  pub major_radius: f32,
+  /// The radius of the tube.
+  pub minor_radius: f32,
+}
+
+#[allow(missing_docs)]
+pub fn new(major_radius: f32, minor_radius: f32) -> T {
+  T {
+    major_radius: major_radius,
+    minor_radius: minor_radius,
+  }
+}
+
+/// A torus filled with a single material.
+pub fn solid<Material>(major_radius: f32, minor_radius: f32, material: Material) -> mosaic::solid::T<Material, T> {
+  mosaic::solid::T {
+    field: new(major_radius, minor_radius),
+    material: material,
+  }
+}
+
+impl T {
+  // The offset of a point from the centre of the tube, away from the axis and along it.
+  fn offset(&self, p: &Point3<f32>) -> Vector2<f32> {
+    Vector2::new(field::radial_distance(p) - self.major_radius, p.y)
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.minor_radius - self.offset(p).magnitude()
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    let offset = self.offset(p);
+    // On the centre of the tube, point away from the axis.
+    let n = if offset.magnitude2() > 0.0 { offset.normalize() } else { Vector2::new(1.0, 0.0) };
+    field::revolved_normal(p, n.x, n.y)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    Some(field::distance_bounds(self, low, high))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{InnerSpace, Point3, Vector3};
+
+  use field::T;
+
+  #[test]
+  fn tori() {
+    let ring = super::new(3.0, 1.0);
+    assert_eq!(ring.density(&Point3::new(0.0, 0.0, 3.0)), 1.0);
+    assert_eq!(ring.density(&Point3::new(0.0, 0.0, 0.0)), -2.0);
+    assert_eq!(ring.density(&Point3::new(-6.0, 4.0, 0.0)), -4.0);
+    assert_eq!(ring.normal(&Point3::new(-6.0, 4.0, 0.0)), Vector3::new(-0.6, 0.8, 0.0));
+    assert_eq!(ring.normal(&Point3::new(0.0, 0.0, 2.5)), Vector3::new(0.0, 0.0, -1.0));
+    assert_eq!(ring.normal(&Point3::new(0.0, 0.0, -3.0)), Vector3::new(0.0, 0.0, -1.0));
+  }
+
+  #[test]
+  fn normals_on_the_axis() {
+    let ring = super::new(3.0, 1.0);
+    assert!((ring.normal(&Point3::new(0.0, 0.0, 0.0)).magnitude() - 1.0).abs() < 1e-6);
+    assert!((ring.normal(&Point3::new(0.0, 2.0, 0.0)).magnitude() - 1.0).abs() < 1e-6);
+  }
+}
@@ -12,13 +12,17 @@ pub struct T<Field> {
 }
 
 impl<Field> field::T for T<Field> where Field: field::T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let p = p + -self.translation;
-    field::T::density(&mut self.field, &p)
+    field::T::density(&self.field, &p)
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
     let p = p + -self.translation;
-    field::T::normal(&mut self.field, &p)
+    field::T::normal(&self.field, &p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    field::T::density_bounds(&self.field, &(low + -self.translation), &(high + -self.translation))
   }
 }
@@ -0,0 +1,68 @@
+//! A field defined by twisting another field around the y axis.
+
+use cgmath::{Point3, Vector3};
+
+use field;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Field> {
+  /// How far the field is turned around the y axis, in radians per unit of height.
+  pub rate: f32,
+  pub field: Field,
+}
+
+impl<Field> T<Field> {
+  fn untwist(&self, p: &Point3<f32>) -> Point3<f32> {
+    let (s, c) = (-self.rate * p.y).sin_cos();
+    Point3::new(c * p.x + s * p.z, p.y, c * p.z - s * p.x)
+  }
+}
+
+impl<Field> field::T for T<Field> where Field: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field, &self.untwist(p))
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::numerical_normal(self, p, field::NORMAL_STEP)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    // Twisting keeps points at the same height and distance from the axis, so the box untwists
+    // to somewhere in the cylinder around it.
+    let x = f32::max(low.x.abs(), high.x.abs());
+    let z = f32::max(low.z.abs(), high.z.abs());
+    let r = (x*x + z*z).sqrt();
+    field::T::density_bounds(&self.field, &Point3::new(-r, low.y, -r), &Point3::new(r, high.y, r))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+  use std::f32;
+
+  use field;
+  use field::T;
+
+  #[test]
+  fn twists() {
+    // A bar along x at the bottom, turning to run along z a quarter turn up.
+    let spire = super::T { rate: f32::consts::FRAC_PI_2, field: field::cuboid::new(Vector3::new(2.0, 4.0, 0.5)) };
+    assert!(spire.density(&Point3::new(1.5, 0.0, 0.0)) > 0.0);
+    assert!(spire.density(&Point3::new(0.0, 0.0, 1.5)) < 0.0);
+    assert!(spire.density(&Point3::new(0.0, 1.0, 1.5)) > 0.0);
+    assert!(spire.density(&Point3::new(1.5, 1.0, 0.0)) < 0.0);
+    let n = spire.normal(&Point3::new(0.0, 0.0, 0.5));
+    assert!((n - Vector3::new(0.0, 0.0, 1.0)).magnitude2() < 1e-3);
+
+    let (low, high) = (Point3::new(0.5, 0.0, -0.5), Point3::new(1.5, 1.0, 0.5));
+    let (min, max) = spire.density_bounds(&low, &high).unwrap();
+    for &p in &[low, high, Point3::new(1.0, 0.5, 0.0), Point3::new(0.5, 1.0, 0.5)] {
+      let d = spire.density(&p);
+      assert!(min <= d && d <= max);
+    }
+    assert!(spire.density_bounds(&Point3::new(0.0, 8.0, 0.0), &Point3::new(1.0, 9.0, 1.0)).unwrap().1 < 0.0);
+  }
+}
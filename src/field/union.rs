@@ -0,0 +1,105 @@
+//! A field defined by the union of other fields, optionally blended smoothly together.
+
+use cgmath::{Point3, Vector3};
+use std::f32;
+
+use field;
+
+#[allow(missing_docs)]
+pub struct T {
+  fields: Vec<Box<dyn field::T>>,
+  /// How close the densities of two fields have to be for them to be blended. Zero gives a
+  /// sharp union.
+  pub blend: f32,
+}
+
+/// An empty sharp union.
+pub fn new() -> T {
+  smooth(0.0)
+}
+
+/// An empty union that blends its fields together; see `field::smooth_max`.
+pub fn smooth(blend: f32) -> T {
+  T {
+    fields: Vec::new(),
+    blend: blend,
+  }
+}
+
+impl T {
+  /// Add a field.
+  pub fn push<Field>(&mut self, field: Field)
+    where Field: field::T + 'static,
+  {
+    self.fields.push(Box::new(field));
+  }
+
+  fn sample(&self, p: &Point3<f32>) -> field::Sample {
+    assert!(!self.fields.is_empty());
+    let first = (self.fields[0].density(p), self.fields[0].normal(p));
+    self.fields[1..].iter().fold(first, |sample, field| {
+      field::smooth_max(sample, (field.density(p), field.normal(p)), self.blend)
+    })
+  }
+}
+
+impl field::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    if self.blend <= 0.0 {
+      assert!(!self.fields.is_empty());
+      return self.fields.iter().fold(f32::NEG_INFINITY, |max, field| f32::max(max, field.density(p)))
+    }
+    self.sample(p).0
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    self.sample(p).1
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    assert!(!self.fields.is_empty());
+    let mut bounds = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for field in &self.fields {
+      let (min, max) = field.density_bounds(low, high)?;
+      bounds = (f32::max(bounds.0, min), f32::max(bounds.1, max));
+    }
+    // Each blend raises the density by at most a quarter of the blend radius.
+    let raise = self.blend.max(0.0) / 4.0 * (self.fields.len() - 1) as f32;
+    Some((bounds.0, bounds.1 + raise))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use field;
+  use field::T;
+
+  fn balls(blend: f32) -> super::T {
+    let mut union = super::smooth(blend);
+    for &x in &[-1.0, 1.0] {
+      union.push(field::translation::T { translation: Vector3::new(x, 0.0, 0.0), field: field::sphere::T { radius: 1.5 } });
+    }
+    union
+  }
+
+  #[test]
+  fn smooth_unions() {
+    let sharp = balls(0.0);
+    assert_eq!(sharp.density(&Point3::new(0.0, 0.0, 0.0)), 1.25);
+    assert_eq!(sharp.density(&Point3::new(2.0, 0.0, 0.0)), 1.25);
+    assert_eq!(sharp.normal(&Point3::new(2.0, 0.0, 0.0)), Vector3::new(1.0, 0.0, 0.0));
+
+    let smooth = balls(2.0);
+    // Where the balls meet, they're blended...
+    assert_eq!(smooth.density(&Point3::new(0.0, 0.0, 0.0)), 1.75);
+    assert!((smooth.normal(&Point3::new(0.0, 1.0, 0.0)) - Vector3::new(0.0, 1.0, 0.0)).magnitude2() < 1e-6);
+    // ...but far from there, they're unchanged.
+    assert_eq!(smooth.density(&Point3::new(2.5, 0.0, 0.0)), 0.0);
+    assert_eq!(smooth.normal(&Point3::new(2.5, 0.0, 0.0)), Vector3::new(1.0, 0.0, 0.0));
+
+    let (min, max) = smooth.density_bounds(&Point3::new(-0.5, -0.5, -0.5), &Point3::new(0.5, 0.5, 0.5)).unwrap();
+    assert!(min <= smooth.density(&Point3::new(0.5, 0.5, 0.5)) && max >= 1.75);
+  }
+}
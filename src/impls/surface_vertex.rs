@@ -1,14 +1,18 @@
 //! A voxel implementation where each voxel stores a single mesh vertex and normal.
 
 use cgmath::{Point3, Vector3, EuclideanSpace, InnerSpace};
+use collision::Aabb;
 use std::cmp::{min, max};
 use std::f32;
+use std::io;
 use std::ops::Neg;
 
 use bounds;
 use brush;
 use field;
 use mosaic;
+use tree;
+use tree::save;
 
 // NOTE: When voxel size and storage become an issue, this should be shrunk to
 // be less than pointer-sized. It'll be easier to transfer to the GPU for
@@ -44,7 +48,7 @@ pub struct SurfaceStruct<Material> {
 /// Create a voxel by sampling a field.
 // TODO: Should this be moved into the general voxel interface?
 pub fn of_field<Material, Mosaic>(
-  field: &mut Mosaic,
+  field: &Mosaic,
   voxel: &bounds::T,
 ) -> T<Option<Material>> where
   Material: Eq + Clone,
@@ -61,7 +65,7 @@ pub fn of_field<Material, Mosaic>(
 
   let corner = material_at!(low, low, low);
   let is_homogenous = {
-    let mut is_homogenous = || {
+    let is_homogenous = || {
       macro_rules! check_corner(($x:expr, $y:expr, $z:expr) => {{
         let material = material_at!($x, $y, $z);
         if material != corner {
@@ -145,48 +149,309 @@ pub fn unwrap<X>(voxel: T<Option<X>>) -> T<X> {
   }
 }
 
+impl<Material> T<Material> {
+  /// The material of this voxel, or of its lowest corner if it crosses the surface.
+  pub fn material(&self) -> &Material {
+    match *self {
+      T::Volume(ref material) => material,
+      T::Surface(ref surface) => &surface.corner,
+    }
+  }
+}
+
+/// Partway between two voxels: surface vertices and normals are interpolated, and anything
+/// else is taken from whichever voxel `weight` is closer to. Volumes and corners can't be
+/// partway, so weights below 0.5 leave them alone; this keeps brushing deterministic.
+pub fn blend<Material>(from: &T<Material>, to: &T<Material>, weight: f32) -> T<Material>
+  where Material: Clone
+{
+  match (from, to) {
+    (T::Surface(from), T::Surface(to)) => {
+      let lerp = |a: Fracu8, b: Fracu8| {
+        Fracu8::of((a.numerator as f32 + (b.numerator as f32 - a.numerator as f32) * weight).round() as u8)
+      };
+      let normal = from.normal.to_float_normal() * (1.0 - weight) + to.normal.to_float_normal() * weight;
+      let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { to.normal.to_float_normal() };
+      T::Surface(SurfaceStruct {
+        surface_vertex: Vertex {
+          x: lerp(from.surface_vertex.x, to.surface_vertex.x),
+          y: lerp(from.surface_vertex.y, to.surface_vertex.y),
+          z: lerp(from.surface_vertex.z, to.surface_vertex.z),
+        },
+        normal: Normal::of_float_normal(&normal),
+        corner: if weight < 0.5 { from.corner.clone() } else { to.corner.clone() },
+      })
+    },
+    _ => if weight < 0.5 { from.clone() } else { to.clone() },
+  }
+}
+
+/// A brush that reshapes existing surfaces, rather than stamping a mosaic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reshape {
+  /// Average each surface vertex and normal with its neighbours'.
+  Smooth,
+  /// Project surface vertices onto a plane.
+  Flatten {
+    #[allow(missing_docs)]
+    point: Point3<f32>,
+    #[allow(missing_docs)]
+    normal: Vector3<f32>,
+  },
+}
+
+/// Reshape the surface voxels of size `brush.min_lg_size` inside `brush.bounds`.
+/// Every voxel is reshaped based on its neighbours as they were before the brush.
+pub fn reshape<Material, OnVoxelUpdate>(
+  tree: &mut tree::T<T<Material>>,
+  brush: &brush::T<()>,
+  reshape: &Reshape,
+  on_voxel_update: &mut OnVoxelUpdate,
+) where
+  Material: Clone,
+  OnVoxelUpdate: FnMut(&T<Material>, &bounds::T),
+{
+  let lg_size = brush.min_lg_size;
+  let each = |p: Point3<i32>, f: &dyn Fn(i32) -> i32| Point3::new(f(p.x), f(p.y), f(p.z));
+  let (low, high) =
+    if lg_size >= 0 {
+      (each(brush.bounds.min(), &|x| x >> lg_size), each(brush.bounds.max(), &|x| ((x - 1) >> lg_size) + 1))
+    } else {
+      (each(brush.bounds.min(), &|x| x << -lg_size), each(brush.bounds.max(), &|x| x << -lg_size))
+    };
+
+  let surface_at = |tree: &tree::T<T<Material>>, voxel: &bounds::T| {
+    match tree.get(voxel) {
+      Some(T::Surface(surface)) => Some(surface.clone()),
+      _ => None,
+    }
+  };
+
+  let mut changes = Vec::new();
+  for x in low.x .. high.x {
+  for y in low.y .. high.y {
+  for z in low.z .. high.z {
+    let voxel = bounds::new(x, y, z, lg_size);
+    let surface =
+      match surface_at(tree, &voxel) {
+        None => continue,
+        Some(surface) => surface,
+      };
+    let weight = brush.weight(&voxel.center());
+    if weight <= 0.0 {
+      continue
+    }
+
+    let (vertex, normal) =
+      match *reshape {
+        Reshape::Smooth => {
+          let mut vertex = Vector3::new(0.0, 0.0, 0.0);
+          let mut normal = Vector3::new(0.0, 0.0, 0.0);
+          let mut count = 0.0;
+          for dx in -1 .. 2 {
+          for dy in -1 .. 2 {
+          for dz in -1 .. 2 {
+            let neighbor = bounds::new(x + dx, y + dy, z + dz, lg_size);
+            if let Some(neighbor_surface) = surface_at(tree, &neighbor) {
+              vertex += neighbor_surface.surface_vertex.to_world_vertex(&neighbor).to_vec();
+              normal += neighbor_surface.normal.to_float_normal();
+              count += 1.0;
+            }
+          }}}
+          (Point3::from_vec(vertex / count), normal)
+        },
+        Reshape::Flatten { point, normal } => {
+          let normal = normal.normalize();
+          let vertex = surface.surface_vertex.to_world_vertex(&voxel);
+          (vertex + normal * -(vertex - point).dot(normal), normal)
+        },
+      };
+    let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { surface.normal.to_float_normal() };
+    let reshaped =
+      T::Surface(SurfaceStruct {
+        surface_vertex: Vertex::of_world_vertex(&vertex, &voxel),
+        normal: Normal::of_float_normal(&normal),
+        corner: surface.corner.clone(),
+      });
+    changes.push((voxel, blend(&T::Surface(surface), &reshaped, weight)));
+  }}}
+
+  for (voxel, reshaped) in changes {
+    if let Some(old) = tree.get_mut(&voxel) {
+      *old = reshaped;
+      on_voxel_update(old, &voxel);
+    }
+  }
+}
+
 impl<Material> ::T<Material> for T<Material> where Material: Eq + Clone {
   fn brush<Mosaic>(
     this: &mut T<Material>,
     bounds: &bounds::T,
-    brush: &mut brush::T<Mosaic>,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
   ) where Mosaic: mosaic::T<Material>
   {
-    let mut set_leaf = |this: &mut T<Material>, corner| {
-      match of_field(&mut brush.mosaic, bounds) {
-        T::Volume(None) => {}
-        T::Volume(Some(material)) => {
-          *this = T::Volume(material);
+    let weight = brush.weight(&bounds.center());
+    if weight <= 0.0 {
+      return
+    }
+    let old = this.clone();
+    brush_hard(this, bounds, brush, mode);
+    if weight < 1.0 {
+      *this = blend(&old, this, weight);
+    }
+  }
+}
+
+// Apply a brush at full strength.
+fn brush_hard<Material, Mosaic>(
+  this: &mut T<Material>,
+  bounds: &bounds::T,
+  brush: &brush::T<Mosaic>,
+  mode: &brush::Mode<Material>,
+) where
+  Material: Eq + Clone,
+  Mosaic: mosaic::T<Material>,
+{
+  let set_leaf = |this: &mut T<Material>, corner| {
+    match of_field(&brush.mosaic, bounds) {
+      T::Volume(None) => {}
+      T::Volume(Some(material)) => {
+        *this = T::Volume(material);
+      },
+      T::Surface(surface) => {
+        let size = bounds.size();
+        let low = Point3::new(bounds.x as f32, bounds.y as f32, bounds.z as f32);
+        let low = low * size;
+        let corner =
+          match mosaic::T::material(&brush.mosaic, &low) {
+            None => corner,
+            Some(material) => material,
+          };
+        let voxel =
+          SurfaceStruct {
+            surface_vertex: surface.surface_vertex,
+            normal: surface.normal,
+            corner: corner,
+          };
+        *this = T::Surface(voxel);
+      },
+    }
+  };
+
+  match *mode {
+    brush::Mode::Add => {
+      let corner = this.material().clone();
+      set_leaf(this, corner);
+    },
+    brush::Mode::Replace { ref filter } => {
+      if this.material() == filter {
+        set_leaf(this, filter.clone());
+      }
+    },
+    brush::Mode::Subtract { ref empty } => {
+      if let T::Volume(ref material) = *this {
+        if material == empty {
+          return
+        }
+      }
+      match of_field(&brush.mosaic, bounds) {
+        T::Volume(None) => {},
+        T::Volume(Some(_)) => {
+          *this = T::Volume(empty.clone());
         },
         T::Surface(surface) => {
-          let size = bounds.size();
-          let low = Point3::new(bounds.x as f32, bounds.y as f32, bounds.z as f32);
-          let low = low * size;
           let corner =
-            match mosaic::T::material(&mut brush.mosaic, &low) {
-              None => corner,
-              Some(material) => material,
+            match mosaic::T::material(&brush.mosaic, &bounds.low_corner()) {
+              None => this.material().clone(),
+              Some(_) => empty.clone(),
             };
-          let voxel =
-            SurfaceStruct {
-              surface_vertex: surface.surface_vertex,
-              normal: surface.normal,
-              corner: corner,
+          // An existing surface the brush doesn't carve away is still a surface of what's left.
+          let kept =
+            match *this {
+              T::Surface(ref old) => {
+                let vertex = old.surface_vertex.to_world_vertex(bounds);
+                if mosaic::T::material(&brush.mosaic, &vertex).is_none() {
+                  Some((old.surface_vertex, old.normal))
+                } else {
+                  None
+                }
+              },
+              T::Volume(_) => None,
             };
-          *this = T::Surface(voxel);
+          // The solid side of the new surface is outside the mosaic, so the normal flips.
+          let (surface_vertex, normal) = kept.unwrap_or((surface.surface_vertex, -surface.normal));
+          *this =
+            T::Surface(SurfaceStruct {
+              surface_vertex: surface_vertex,
+              normal: normal,
+              corner: corner,
+            });
         },
       }
-    };
+    },
+    brush::Mode::Paint { ref empty } => {
+      if this.material() == empty {
+        return
+      }
+      if let Some(material) = mosaic::T::material(&brush.mosaic, &bounds.low_corner()) {
+        match *this {
+          T::Volume(ref mut old) => *old = material,
+          T::Surface(ref mut surface) => surface.corner = material,
+        }
+      }
+    },
+  }
+}
 
-    match this.clone() {
+impl<Material> save::Voxel for T<Material> where Material: save::Voxel {
+  fn type_id() -> String {
+    format!("surface_vertex<{}>", Material::type_id())
+  }
+
+  fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    match *self {
       T::Volume(ref material) => {
-        set_leaf(this, material.clone());
+        w.write_all(&[0])?;
+        material.write(w)
       },
-      T::Surface(ref voxel) => {
-        set_leaf(this, voxel.corner.clone());
+      T::Surface(ref surface) => {
+        let v = &surface.surface_vertex;
+        let n = &surface.normal;
+        w.write_all(&[
+          1,
+          v.x.numerator, v.y.numerator, v.z.numerator,
+          n.x.numerator as u8, n.y.numerator as u8, n.z.numerator as u8,
+        ])?;
+        surface.corner.write(w)
       },
     }
   }
+
+  fn read<R: io::Read>(r: &mut R) -> io::Result<Self> {
+    match save::read_u8(r)? {
+      0 => Ok(T::Volume(Material::read(r)?)),
+      1 => {
+        let mut bytes = [0; 6];
+        r.read_exact(&mut bytes)?;
+        Ok(T::Surface(SurfaceStruct {
+          surface_vertex: Vertex {
+            x: Fracu8::of(bytes[0]),
+            y: Fracu8::of(bytes[1]),
+            z: Fracu8::of(bytes[2]),
+          },
+          normal: Normal {
+            x: Fraci8::of(bytes[3] as i8),
+            y: Fraci8::of(bytes[4] as i8),
+            z: Fraci8::of(bytes[5] as i8),
+          },
+          corner: Material::read(r)?,
+        }))
+      },
+      tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad surface_vertex tag {}", tag))),
+    }
+  }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -213,6 +478,18 @@ impl Vertex {
     let fparent = Point3::new(parent.x as f32, parent.y as f32, parent.z as f32);
     (fparent + local) * parent.size()
   }
+
+  /// The vertex closest to a world position, inside a given voxel.
+  pub fn of_world_vertex(p: &Point3<f32>, parent: &bounds::T) -> Vertex {
+    let fparent = Vector3::new(parent.x as f32, parent.y as f32, parent.z as f32);
+    let local = (p.to_vec() / parent.size() - fparent) * 256.0;
+    let frac = |x: f32| Fracu8::of(x.round().clamp(0.0, 255.0) as u8);
+    Vertex {
+      x: frac(local.x),
+      y: frac(local.y),
+      z: frac(local.z),
+    }
+  }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -295,3 +572,182 @@ impl Fraci8 {
     self.numerator as f32 / 128.0
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use bounds;
+  use brush;
+  use field;
+  use mosaic;
+  use tree;
+
+  use super::T;
+
+  const AIR: u8 = 0;
+  const STONE: u8 = 1;
+  const DIRT: u8 = 2;
+
+  fn sphere() -> brush::T<mosaic::solid::T<u8, field::sphere::T>> {
+    brush::new(
+      brush::Bounds::new(Point3::new(-2, -2, -2), Point3::new(2, 2, 2)),
+      mosaic::solid::T { field: field::sphere::T { radius: 2.0 }, material: STONE },
+      0,
+    )
+  }
+
+  fn brushed(voxel: T<u8>, bounds: &bounds::T, mode: brush::Mode<u8>) -> T<u8> {
+    let mut voxel = voxel;
+    ::T::brush(&mut voxel, bounds, &sphere(), &mode);
+    voxel
+  }
+
+  #[test]
+  fn brush_modes() {
+    let inside = bounds::new(0, 0, 0, 0);
+    let surface = bounds::new(1, 1, 1, 0);
+
+    assert_eq!(brushed(T::Volume(AIR), &inside, brush::Mode::Add), T::Volume(STONE));
+    assert_eq!(brushed(T::Volume(AIR), &inside, brush::Mode::Replace { filter: DIRT }), T::Volume(AIR));
+    assert_eq!(brushed(T::Volume(DIRT), &inside, brush::Mode::Replace { filter: DIRT }), T::Volume(STONE));
+    assert_eq!(brushed(T::Volume(DIRT), &inside, brush::Mode::Subtract { empty: AIR }), T::Volume(AIR));
+    assert_eq!(brushed(T::Volume(DIRT), &inside, brush::Mode::Paint { empty: AIR }), T::Volume(STONE));
+    assert_eq!(brushed(T::Volume(AIR), &inside, brush::Mode::Paint { empty: AIR }), T::Volume(AIR));
+
+    let added = brushed(T::Volume(AIR), &surface, brush::Mode::Add);
+    let carved = brushed(T::Volume(DIRT), &surface, brush::Mode::Subtract { empty: AIR });
+    match (added, carved) {
+      (T::Surface(added), T::Surface(carved)) => {
+        assert_eq!(added.corner, STONE);
+        assert_eq!(carved.corner, AIR);
+        assert_eq!(carved.surface_vertex, added.surface_vertex);
+        assert_eq!(carved.normal, -added.normal);
+
+        // Painting changes the material, but not the surface.
+        let dirt = T::Surface(super::SurfaceStruct { corner: DIRT, ..added });
+        assert_eq!(brushed(dirt, &surface, brush::Mode::Paint { empty: AIR }), T::Surface(added));
+      },
+      voxels => panic!("expected surfaces, got {:?}", voxels),
+    }
+  }
+
+  #[test]
+  fn carving_next_to_a_surface_keeps_it() {
+    let voxel = bounds::new(1, 1, 1, 0);
+    let existing = |n| {
+      super::SurfaceStruct {
+        surface_vertex: super::Vertex { x: super::Fracu8::of(n), y: super::Fracu8::of(n), z: super::Fracu8::of(n) },
+        normal: super::Normal::of_float_normal(&Vector3::new(0.0, 1.0, 0.0)),
+        corner: DIRT,
+      }
+    };
+    let carved =
+      match brushed(T::Volume(DIRT), &voxel, brush::Mode::Subtract { empty: AIR }) {
+        T::Surface(carved) => carved,
+        voxel => panic!("expected a surface, got {:?}", voxel),
+      };
+
+    // This vertex is outside the sphere, so the existing surface survives the carving.
+    let far = existing(200);
+    assert_eq!(
+      brushed(T::Surface(far), &voxel, brush::Mode::Subtract { empty: AIR }),
+      T::Surface(super::SurfaceStruct { corner: AIR, ..far }),
+    );
+
+    // This one is carved away, leaving the sphere's surface.
+    let near = existing(10);
+    assert_eq!(brushed(T::Surface(near), &voxel, brush::Mode::Subtract { empty: AIR }), T::Surface(carved));
+  }
+
+  #[test]
+  fn soft_brushes_blend() {
+    let surface = bounds::new(1, 1, 1, 0);
+    let old =
+      super::SurfaceStruct {
+        surface_vertex: super::Vertex { x: super::Fracu8::of(0), y: super::Fracu8::of(0), z: super::Fracu8::of(0) },
+        normal: super::Normal::of_float_normal(&Vector3::new(1.0, 0.0, 0.0)),
+        corner: DIRT,
+      };
+    let hard =
+      match brushed(T::Surface(old), &surface, brush::Mode::Add) {
+        T::Surface(hard) => hard,
+        voxel => panic!("expected a surface, got {:?}", voxel),
+      };
+
+    let mut brush = sphere();
+    brush.strength = 0.5;
+    let mut voxel = T::Surface(old);
+    ::T::brush(&mut voxel, &surface, &brush, &brush::Mode::Add);
+    match voxel {
+      T::Surface(soft) => {
+        let halfway = |a: super::Fracu8, b: super::Fracu8| (a.numerator as i32 + b.numerator as i32 + 1) / 2;
+        assert_eq!(soft.surface_vertex.x.numerator as i32, halfway(old.surface_vertex.x, hard.surface_vertex.x));
+        assert_eq!(soft.corner, hard.corner);
+      },
+      voxel => panic!("expected a surface, got {:?}", voxel),
+    }
+
+    // Past the falloff radius, nothing changes.
+    brush.strength = 1.0;
+    brush.falloff = Some(brush::Falloff::within(&brush.bounds, brush::Profile::Linear));
+    assert_eq!(brush.weight(&Point3::new(0.0, 0.0, 0.0)), 1.0);
+    assert_eq!(brush.weight(&Point3::new(1.0, 0.0, 0.0)), 0.5);
+    let outside = bounds::new(1, 1, 2, 0);
+    let mut voxel = T::Volume(AIR);
+    ::T::brush(&mut voxel, &outside, &brush, &brush::Mode::Add);
+    assert_eq!(voxel, T::Volume(AIR));
+  }
+
+  #[test]
+  fn weak_brushes_leave_volumes_alone() {
+    let inside = bounds::new(0, 0, 0, 0);
+    let mut brush = sphere();
+    brush.strength = 0.25;
+    let mut voxel = T::Volume(AIR);
+    ::T::brush(&mut voxel, &inside, &brush, &brush::Mode::Add);
+    assert_eq!(voxel, T::Volume(AIR));
+
+    brush.strength = 0.5;
+    ::T::brush(&mut voxel, &inside, &brush, &brush::Mode::Add);
+    assert_eq!(voxel, T::Volume(STONE));
+  }
+
+  fn ground(y: u8) -> T<u8> {
+    T::Surface(super::SurfaceStruct {
+      surface_vertex: super::Vertex { x: super::Fracu8::of(128), y: super::Fracu8::of(y), z: super::Fracu8::of(128) },
+      normal: super::Normal::of_float_normal(&Vector3::new(0.0, 1.0, 0.0)),
+      corner: DIRT,
+    })
+  }
+
+  fn height(tree: &tree::T<T<u8>>, x: i32) -> u8 {
+    match tree.get(&bounds::new(x, 0, 0, 0)) {
+      Some(T::Surface(surface)) => surface.surface_vertex.y.numerator,
+      voxel => panic!("expected a surface, got {:?}", voxel),
+    }
+  }
+
+  #[test]
+  fn reshape_surfaces() {
+    let mut tree = tree::new();
+    for x in 0 .. 5 {
+      tree.get_mut_or_create(&bounds::new(x, 0, 0, 0)).data = Some(ground(if x == 2 { 254 } else { 128 }));
+    }
+    let brush = brush::new(brush::Bounds::new(Point3::new(1, 0, 0), Point3::new(4, 1, 1)), (), 0);
+
+    let mut updates = 0;
+    super::reshape(&mut tree, &brush, &super::Reshape::Smooth, &mut |_, _| updates += 1);
+    assert_eq!(updates, 3);
+    assert!(height(&tree, 2) < 254 && height(&tree, 2) > 128);
+    assert!(height(&tree, 1) > 128);
+    assert_eq!(height(&tree, 0), 128);
+
+    let plane = super::Reshape::Flatten { point: Point3::new(0.0, 0.25, 0.0), normal: Vector3::new(0.0, 2.0, 0.0) };
+    super::reshape(&mut tree, &brush, &plane, &mut |_, _| {});
+    for x in 1 .. 4 {
+      assert_eq!(height(&tree, x), 64);
+    }
+    assert_eq!(height(&tree, 4), 128);
+  }
+}
@@ -9,6 +9,8 @@ extern crate cgmath;
 extern crate collision;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -17,8 +19,11 @@ extern crate test;
 
 pub mod bounds;
 pub mod brush;
+pub mod command;
 pub mod field;
 pub mod mosaic;
+pub mod paged;
+pub mod sharded;
 pub mod tree;
 
 pub mod impls;
@@ -29,6 +34,7 @@ pub trait T<Material> {
   fn brush<Mosaic>(
     this: &mut Self,
     bounds: &bounds::T,
-    brush: &mut brush::T<Mosaic>,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
   ) where Mosaic: mosaic::T<Material>;
 }
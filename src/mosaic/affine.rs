@@ -0,0 +1,80 @@
+//! Apply an arbitrary affine transformation to another voxel mosaic.
+
+use cgmath::{Point3, Vector3, Matrix3, Matrix4, Decomposed, Rotation3};
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Mosaic> {
+  /// The transformed mosaic, as a field.
+  pub field: field::affine::T<Mosaic>,
+}
+
+/// Transform a mosaic by `transform`, or `None` if it isn't invertible.
+pub fn new<Mosaic>(transform: Matrix4<f32>, mosaic: Mosaic) -> Option<T<Mosaic>> {
+  Some(T {
+    field: field::affine::new(transform, mosaic)?,
+  })
+}
+
+/// Transform a mosaic by a scale, rotation and translation, or `None` if the scale is zero.
+pub fn of_decomposed<Mosaic, Rotation>(transform: Decomposed<Vector3<f32>, Rotation>, mosaic: Mosaic) -> Option<T<Mosaic>>
+  where Rotation: Rotation3<f32> + Into<Matrix3<f32>>,
+{
+  Some(T {
+    field: field::affine::of_decomposed(transform, mosaic)?,
+  })
+}
+
+impl<Mosaic> field::T for T<Mosaic> where Mosaic: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field, p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::T::normal(&self.field, p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    field::T::density_bounds(&self.field, low, high)
+  }
+}
+
+impl<Mosaic, Material> mosaic::T<Material> for T<Mosaic> where Mosaic: mosaic::T<Material> {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    self.field.density_scale() * mosaic::T::density(&self.field.field, &self.field.to_local(p))
+  }
+
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
+    mosaic::T::material(&self.field.field, &self.field.to_local(p))
+  }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    let (low, high) = field::transform_box(low, high, |p| self.field.to_local(&p));
+    mosaic::T::homogeneous(&self.field.field, &low, &high)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, Decomposed, Quaternion, Matrix4};
+
+  use field;
+  use mosaic;
+
+  #[test]
+  fn transformed_mosaics() {
+    let ball = mosaic::solid::T { field: field::sphere::T { radius: 1.0 }, material: 3 };
+    let placed =
+      super::of_decomposed(
+        Decomposed { scale: 2.0, rot: Quaternion::new(1.0, 0.0, 0.0, 0.0), disp: Vector3::new(5.0, 0.0, 0.0) },
+        ball,
+      ).unwrap();
+    assert_eq!(mosaic::T::material(&placed, &Point3::new(6.5, 0.0, 0.0)), Some(3));
+    assert_eq!(mosaic::T::material(&placed, &Point3::new(7.5, 0.0, 0.0)), None);
+    assert_eq!(mosaic::T::density(&placed, &Point3::new(5.0, 0.0, 0.0)), 2.0);
+    assert!(super::new(Matrix4::from_scale(0.0), ball).is_none());
+  }
+}
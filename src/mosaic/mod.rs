@@ -1,8 +1,11 @@
 //! A density field that also defines materials. This does not need to be defined everywhere.
 
 use cgmath::{Point3};
-use std::ops::DerefMut;
+use std::ops::Deref;
 
+pub mod affine;
+pub mod rotation;
+pub mod scale;
 pub mod solid;
 pub mod union;
 pub mod translation;
@@ -12,20 +15,45 @@ use field;
 #[allow(missing_docs)]
 pub trait T<Material>: field::T {
   /// The material density at a given point. This should be nonnegative!
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     field::T::density(self, p).abs()
   }
 
   /// The material at this point.
-  fn material(&mut self, p: &Point3<f32>) -> Option<Material>;
+  fn material(&self, p: &Point3<f32>) -> Option<Material>;
+
+  /// If the material is certainly the same everywhere in the box from `low` to `high`, that
+  /// material (`None` if the box is outside the mosaic). Brushes don't refine boxes like this.
+  /// Returning `None` is always correct.
+  fn homogeneous(&self, _low: &Point3<f32>, _high: &Point3<f32>) -> Option<Option<Material>> {
+    None
+  }
 }
 
 impl<X: ?Sized, Material> T<Material> for Box<X> where X: T<Material> {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
-    T::density(self.deref_mut(), p)
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    T::density(self.deref(), p)
+  }
+
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
+    T::material(self.deref(), p)
+  }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    T::homogeneous(self.deref(), low, high)
+  }
+}
+
+impl<X: ?Sized, Material> T<Material> for &X where X: T<Material> {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    T::density(*self, p)
+  }
+
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
+    T::material(*self, p)
   }
 
-  fn material(&mut self, p: &Point3<f32>) -> Option<Material> {
-    T::material(self.deref_mut(), p)
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    T::homogeneous(*self, low, high)
   }
 }
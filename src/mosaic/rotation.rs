@@ -0,0 +1,81 @@
+//! Rotate another voxel mosaic.
+
+use cgmath::{Point3, Vector3, Rotation, Basis3};
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Mosaic> {
+  pub rotation: Basis3<f32>,
+  pub mosaic: Mosaic,
+}
+
+impl<Mosaic> T<Mosaic> {
+  fn field(&self) -> field::rotation::T<&Mosaic> {
+    field::rotation::T {
+      rotation: self.rotation,
+      field: &self.mosaic,
+    }
+  }
+}
+
+impl<Mosaic> field::T for T<Mosaic> where Mosaic: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field(), p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::T::normal(&self.field(), p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    field::T::density_bounds(&self.field(), low, high)
+  }
+}
+
+impl<Mosaic, Material> mosaic::T<Material> for T<Mosaic> where Mosaic: mosaic::T<Material> {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    let p = self.rotation.invert().rotate_point(*p);
+    mosaic::T::density(&self.mosaic, &p)
+  }
+
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
+    let p = self.rotation.invert().rotate_point(*p);
+    mosaic::T::material(&self.mosaic, &p)
+  }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    let rotation = self.rotation.invert();
+    let (low, high) = field::transform_box(low, high, |p| rotation.rotate_point(p));
+    mosaic::T::homogeneous(&self.mosaic, &low, &high)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3, Basis3, Rad, Rotation3, InnerSpace};
+  use std::f32;
+
+  use field;
+  use mosaic;
+
+  #[test]
+  fn rotated_unions() {
+    let mut prefab = mosaic::union::new();
+    prefab.push(1, field::translation::T { translation: Vector3::new(2.0, 0.0, 0.0), field: field::sphere::T { radius: 1.0 } });
+    prefab.push(2, field::translation::T { translation: Vector3::new(-2.0, 0.0, 0.0), field: field::sphere::T { radius: 1.0 } });
+    let rotated =
+      super::T {
+        rotation: Basis3::from_angle_z(Rad(f32::consts::FRAC_PI_2)),
+        mosaic: prefab,
+      };
+
+    assert_eq!(mosaic::T::material(&rotated, &Point3::new(0.0, 2.0, 0.0)), Some(1));
+    assert_eq!(mosaic::T::material(&rotated, &Point3::new(0.0, -2.0, 0.0)), Some(2));
+    assert_eq!(mosaic::T::material(&rotated, &Point3::new(2.0, 0.0, 0.0)), None);
+    let n = field::T::normal(&rotated, &Point3::new(0.0, 3.0, 0.0));
+    assert!((n - Vector3::new(0.0, 1.0, 0.0)).magnitude2() < 1e-6);
+  }
+}
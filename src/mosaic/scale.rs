@@ -0,0 +1,85 @@
+//! Scale another voxel mosaic, possibly by a different amount along each axis.
+
+use cgmath::{Point3, Vector3};
+
+use field;
+use mosaic;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct T<Mosaic> {
+  /// The scale along each axis. None of these should be zero.
+  pub scale: Vector3<f32>,
+  pub mosaic: Mosaic,
+}
+
+/// Scale a mosaic by the same amount along every axis.
+pub fn uniform<Mosaic>(scale: f32, mosaic: Mosaic) -> T<Mosaic> {
+  T {
+    scale: Vector3::new(scale, scale, scale),
+    mosaic: mosaic,
+  }
+}
+
+impl<Mosaic> T<Mosaic> {
+  fn field(&self) -> field::scale::T<&Mosaic> {
+    field::scale::T {
+      scale: self.scale,
+      field: &self.mosaic,
+    }
+  }
+}
+
+impl<Mosaic> field::T for T<Mosaic> where Mosaic: field::T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field(), p)
+  }
+
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::T::normal(&self.field(), p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    field::T::density_bounds(&self.field(), low, high)
+  }
+}
+
+impl<Mosaic, Material> mosaic::T<Material> for T<Mosaic> where Mosaic: mosaic::T<Material> {
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    let field = self.field();
+    field.density_scale() * mosaic::T::density(&self.mosaic, &field.unscale(p))
+  }
+
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
+    mosaic::T::material(&self.mosaic, &self.field().unscale(p))
+  }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    let field = self.field();
+    let (low, high) = field::transform_box(low, high, |p| field.unscale(&p));
+    mosaic::T::homogeneous(&self.mosaic, &low, &high)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+
+  use field;
+  use mosaic;
+
+  #[test]
+  fn scaled_mosaics() {
+    let ball = mosaic::solid::T { field: field::sphere::T { radius: 1.0 }, material: 3 };
+    let squashed = super::T { scale: Vector3::new(1.0, 0.5, 1.0), mosaic: ball };
+    assert_eq!(mosaic::T::material(&squashed, &Point3::new(0.9, 0.0, 0.0)), Some(3));
+    assert_eq!(mosaic::T::material(&squashed, &Point3::new(0.0, 0.9, 0.0)), None);
+    // Densities shrink with the smallest scale.
+    assert_eq!(mosaic::T::density(&squashed, &Point3::new(0.0, 0.0, 0.0)), 0.5);
+    assert_eq!(field::T::normal(&squashed, &Point3::new(0.0, 0.5, 0.0)), Vector3::new(0.0, 1.0, 0.0));
+
+    let grown = super::uniform(2.0, ball);
+    assert_eq!(mosaic::T::material(&grown, &Point3::new(0.0, 1.9, 0.0)), Some(3));
+    assert_eq!(mosaic::T::homogeneous(&grown, &Point3::new(3.0, 3.0, 3.0), &Point3::new(4.0, 4.0, 4.0)), Some(None));
+  }
+}
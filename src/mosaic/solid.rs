@@ -12,27 +12,43 @@ pub struct T<Material, Field> {
   pub material: Material,
 }
 
-unsafe impl<Material, Field> Send for T<Material, Field> where Field: Send {}
+impl<Material, Field> field::T for T<Material, Field> where
+  Field: field::T,
+  Material: Send + Sync,
+{
+  fn density(&self, p: &Point3<f32>) -> f32 {
+    field::T::density(&self.field, p)
+  }
 
-impl<Material, Field> field::T for T<Material, Field> where Field: field::T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
-    field::T::density(&mut self.field, p)
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    field::T::normal(&self.field, p)
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
-    field::T::normal(&mut self.field, p)
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    field::T::density_bounds(&self.field, low, high)
   }
 }
 
 impl<Material, Field> mosaic::T<Material> for T<Material, Field> where
   Field: field::T,
-  Material: Clone,
+  Material: Clone + Send + Sync,
 {
-  fn material(&mut self, p: &Point3<f32>) -> Option<Material> {
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
     if field::T::density(self, p) >= 0.0 {
       Some(self.material.clone())
     } else {
       None
     }
   }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    let (min, max) = field::T::density_bounds(self, low, high)?;
+    if min >= 0.0 {
+      Some(Some(self.material.clone()))
+    } else if max < 0.0 {
+      Some(None)
+    } else {
+      None
+    }
+  }
 }
@@ -13,25 +13,33 @@ pub struct T<Mosaic> {
 }
 
 impl<Mosaic> field::T for T<Mosaic> where Mosaic: field::T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let p = p + -self.translation;
-    field::T::density(&mut self.mosaic, &p)
+    field::T::density(&self.mosaic, &p)
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
     let p = p + -self.translation;
-    field::T::normal(&mut self.mosaic, &p)
+    field::T::normal(&self.mosaic, &p)
+  }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    field::T::density_bounds(&self.mosaic, &(low + -self.translation), &(high + -self.translation))
   }
 }
 
 impl<Mosaic, Material> mosaic::T<Material> for T<Mosaic> where Mosaic: mosaic::T<Material> {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let p = p + -self.translation;
-    mosaic::T::density(&mut self.mosaic, &p)
+    mosaic::T::density(&self.mosaic, &p)
   }
 
-  fn material(&mut self, p: &Point3<f32>) -> Option<Material> {
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
     let p = p + -self.translation;
-    mosaic::T::material(&mut self.mosaic, &p)
+    mosaic::T::material(&self.mosaic, &p)
+  }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    mosaic::T::homogeneous(&self.mosaic, &(low + -self.translation), &(high + -self.translation))
   }
 }
@@ -8,11 +8,9 @@ use mosaic;
 
 #[allow(missing_docs)]
 pub struct T<Material> {
-  components: Vec<(Box<field::T>, Material)>,
+  components: Vec<(Box<dyn field::T>, Material)>,
 }
 
-unsafe impl<Material> Send for T<Material> {}
-
 #[allow(missing_docs)]
 pub fn new<Material>() -> T<Material> {
   T {
@@ -29,21 +27,21 @@ impl<Material> T<Material> {
   }
 }
 
-impl<Material> field::T for T<Material> {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+impl<Material> field::T for T<Material> where Material: Send + Sync {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     assert!(!self.components.is_empty());
-    self.components.iter_mut().fold(
+    self.components.iter().fold(
       f32::NEG_INFINITY,
-      |max, &mut (ref mut shape, _)| f32::max(max, shape.density(p)),
+      |max, (shape, _)| f32::max(max, shape.density(p)),
     )
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
     assert!(!self.components.is_empty());
     let (_, normal) =
-      self.components.iter_mut().fold(
+      self.components.iter().fold(
         (f32::NEG_INFINITY, Vector3::new(0.0, 0.0, 0.0)),
-        |(max, normal), &mut (ref mut shape, _)| {
+        |(max, normal), (shape, _)| {
           let d = shape.density(p);
           if d > max {
             (d, shape.normal(p))
@@ -54,15 +52,25 @@ impl<Material> field::T for T<Material> {
       );
     normal
   }
+
+  fn density_bounds(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<(f32, f32)> {
+    assert!(!self.components.is_empty());
+    let mut bounds = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for (shape, _) in &self.components {
+      let (min, max) = shape.density_bounds(low, high)?;
+      bounds = (f32::max(bounds.0, min), f32::max(bounds.1, max));
+    }
+    Some(bounds)
+  }
 }
 
-impl<Material> mosaic::T<Material> for T<Material> where Material: Eq + Clone {
-  fn material(&mut self, p: &Point3<f32>) -> Option<Material> {
+impl<Material> mosaic::T<Material> for T<Material> where Material: Eq + Clone + Send + Sync {
+  fn material(&self, p: &Point3<f32>) -> Option<Material> {
     assert!(!self.components.is_empty());
     let (_, material) =
-      self.components.iter_mut().fold(
+      self.components.iter().fold(
         (f32::NEG_INFINITY, None),
-        |(max, max_material), &mut (ref mut shape, ref material)| {
+        |(max, max_material), (shape, material)| {
           let d = shape.density(p);
           if d > max && d >= 0.0 {
             (d, Some(material.clone()))
@@ -73,4 +81,25 @@ impl<Material> mosaic::T<Material> for T<Material> where Material: Eq + Clone {
       );
     material
   }
+
+  fn homogeneous(&self, low: &Point3<f32>, high: &Point3<f32>) -> Option<Option<Material>> {
+    assert!(!self.components.is_empty());
+    let mut bounds = Vec::with_capacity(self.components.len());
+    for (shape, _) in &self.components {
+      bounds.push(shape.density_bounds(low, high)?);
+    }
+    if bounds.iter().all(|&(_, max)| max < 0.0) {
+      return Some(None)
+    }
+    // The material is homogeneous if one component is denser than all the others throughout.
+    for (i, &(min, _)) in bounds.iter().enumerate() {
+      let dominates =
+        min >= 0.0 &&
+        bounds.iter().enumerate().all(|(j, &(_, max))| i == j || max < min);
+      if dominates {
+        return Some(Some(self.components[i].1.clone()))
+      }
+    }
+    None
+  }
 }
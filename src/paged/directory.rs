@@ -0,0 +1,91 @@
+//! A region store keeping one file per region in a directory.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bounds;
+use paged;
+use tree;
+use tree::codec;
+use tree::region;
+use tree::save;
+
+#[allow(missing_docs)]
+pub struct T {
+  path: PathBuf,
+}
+
+/// Use the directory at `path` as a region store, creating it if it doesn't exist.
+pub fn new<P: AsRef<Path>>(path: P) -> io::Result<T> {
+  fs::create_dir_all(path.as_ref())?;
+  Ok(T {
+    path: path.as_ref().to_path_buf(),
+  })
+}
+
+impl T {
+  fn file_name(region: &bounds::T) -> String {
+    format!("{}_{}_{}_{}.region", region.x, region.y, region.z, region.lg_size)
+  }
+
+  fn parse_file_name(name: &str) -> Option<bounds::T> {
+    if !name.ends_with(".region") {
+      return None
+    }
+    let coords: Vec<&str> = name[.. name.len() - ".region".len()].split('_').collect();
+    if coords.len() != 4 {
+      return None
+    }
+    Some(bounds::new(
+      coords[0].parse().ok()?,
+      coords[1].parse().ok()?,
+      coords[2].parse().ok()?,
+      coords[3].parse().ok()?,
+    ))
+  }
+
+  /// The file a region is stored in.
+  pub fn path_of(&self, region: &bounds::T) -> PathBuf {
+    self.path.join(T::file_name(region))
+  }
+}
+
+impl<Voxel> paged::Store<Voxel> for T where Voxel: save::Voxel + PartialEq + Clone {
+  fn save(&mut self, region: &bounds::T, tree: &tree::T<Voxel>) -> Result<(), save::Error> {
+    // Write to a temporary file first, so a failed write doesn't clobber the old region.
+    let tmp = self.path_of(region).with_extension("tmp");
+    {
+      let mut w = io::BufWriter::new(fs::File::create(&tmp)?);
+      region::write(&mut w, tree, region, codec::Compression::RunLength)?;
+      io::Write::flush(&mut w)?;
+    }
+    fs::rename(&tmp, self.path_of(region))?;
+    Ok(())
+  }
+
+  fn load(&mut self, region: &bounds::T, tree: &mut tree::T<Voxel>) -> Result<bool, save::Error> {
+    let file =
+      match fs::File::open(self.path_of(region)) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(save::Error::Io(e)),
+      };
+    let loaded = region::read(&mut io::BufReader::new(file), tree)?;
+    if loaded != *region {
+      return Err(save::Error::Io(io::Error::new(io::ErrorKind::InvalidData, "region file holds the wrong region")))
+    }
+    Ok(true)
+  }
+
+  fn regions(&self) -> Result<Vec<bounds::T>, save::Error> {
+    let mut regions = Vec::new();
+    for entry in fs::read_dir(&self.path)? {
+      let entry = entry?;
+      if let Some(region) = entry.file_name().to_str().and_then(T::parse_file_name) {
+        regions.push(region);
+      }
+    }
+    Ok(regions)
+  }
+}
@@ -0,0 +1,488 @@
+//! A voxel tree whose regions can be paged out to a store, and faulted back in on demand.
+//!
+//! Space is divided into regions of a fixed `lg_size`. Operations that touch a region load it
+//! from the store first; once more than `capacity` regions are loaded, the least recently used
+//! ones are written back (if they've been modified) and dropped from memory.
+
+use cgmath::Point3;
+use collision::{Aabb, Ray3};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+use bounds;
+use brush;
+use mosaic;
+use tree;
+use tree::save;
+
+pub mod directory;
+
+/// Somewhere to keep regions that aren't in memory.
+pub trait Store<Voxel> {
+  /// Write back a modified region from `tree`.
+  fn save(&mut self, region: &bounds::T, tree: &tree::T<Voxel>) -> Result<(), save::Error>;
+
+  /// Load a region into `tree`. Returns false if the store doesn't have the region.
+  fn load(&mut self, region: &bounds::T, tree: &mut tree::T<Voxel>) -> Result<bool, save::Error>;
+
+  /// Every region the store has.
+  fn regions(&self) -> Result<Vec<bounds::T>, save::Error>;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Residency {
+  last_used: u64,
+  dirty: bool,
+}
+
+#[allow(missing_docs)]
+pub struct T<Voxel, Store> {
+  tree: tree::T<Voxel>,
+  store: Store,
+  region_lg_size: i16,
+  capacity: usize,
+  resident: HashMap<bounds::T, Residency>,
+  // The regions in the store, read from it the first time they're needed.
+  stored: Option<HashSet<bounds::T>>,
+  // The lowest and highest region coordinates that have been stored or resident, if any have.
+  extent: Option<(Point3<i32>, Point3<i32>)>,
+  clock: u64,
+}
+
+/// Create a paged tree with regions of size `2^region_lg_size`,
+/// keeping at most `capacity` regions in memory between operations.
+pub fn new<Voxel, Store>(store: Store, region_lg_size: i16, capacity: usize) -> T<Voxel, Store> {
+  assert!(region_lg_size >= 0);
+  assert!(capacity > 0);
+  T {
+    tree: tree::new(),
+    store: store,
+    region_lg_size: region_lg_size,
+    capacity: capacity,
+    resident: HashMap::new(),
+    stored: None,
+    extent: None,
+    clock: 0,
+  }
+}
+
+impl<Voxel, Store> T<Voxel, Store> where Store: self::Store<Voxel> {
+  /// The in-memory tree. Regions that aren't resident appear empty.
+  pub fn tree(&self) -> &tree::T<Voxel> {
+    &self.tree
+  }
+
+  #[allow(missing_docs)]
+  pub fn store(&self) -> &Store {
+    &self.store
+  }
+
+  /// The regions currently in memory.
+  pub fn resident(&self) -> Vec<bounds::T> {
+    self.resident.keys().cloned().collect()
+  }
+
+  /// The region containing a voxel, or `None` if the voxel is larger than a region.
+  pub fn region_of(&self, voxel: &bounds::T) -> Option<bounds::T> {
+    if voxel.lg_size > self.region_lg_size {
+      return None
+    }
+    let shift = self.region_lg_size - voxel.lg_size;
+    Some(bounds::new(voxel.x >> shift, voxel.y >> shift, voxel.z >> shift, self.region_lg_size))
+  }
+
+  fn stored(&mut self) -> Result<&mut HashSet<bounds::T>, save::Error> {
+    if self.stored.is_none() {
+      let stored: HashSet<bounds::T> = self.store.regions()?.into_iter().collect();
+      for region in &stored {
+        self.extend(region);
+      }
+      self.stored = Some(stored);
+    }
+    Ok(self.stored.as_mut().unwrap())
+  }
+
+  fn extend(&mut self, region: &bounds::T) {
+    let p = Point3::new(region.x, region.y, region.z);
+    self.extent =
+      match self.extent {
+        None => Some((p, p)),
+        Some((low, high)) => Some((
+          Point3::new(cmp::min(low.x, p.x), cmp::min(low.y, p.y), cmp::min(low.z, p.z)),
+          Point3::new(cmp::max(high.x, p.x), cmp::max(high.y, p.y), cmp::max(high.z, p.z)),
+        )),
+      };
+  }
+
+  fn save(&mut self, region: &bounds::T) -> Result<(), save::Error> {
+    self.store.save(region, &self.tree)?;
+    if let Some(ref mut stored) = self.stored {
+      stored.insert(*region);
+    }
+    Ok(())
+  }
+
+  /// Make sure a region is in memory, and mark it as recently used.
+  pub fn fault_in(&mut self, region: &bounds::T) -> Result<(), save::Error> {
+    self.clock += 1;
+    if let Some(residency) = self.resident.get_mut(region) {
+      residency.last_used = self.clock;
+      return Ok(())
+    }
+
+    debug!("faulting in {:?}", region);
+    // Resident regions are always whole nodes, so they can be saved and blanked.
+    self.tree.grow_to_hold(region);
+    self.store.load(region, &mut self.tree)?;
+    self.resident.insert(*region, Residency { last_used: self.clock, dirty: false });
+    self.extend(region);
+    Ok(())
+  }
+
+  /// Mark a resident region as modified, so it's written back when it's evicted.
+  pub fn mark_dirty(&mut self, region: &bounds::T) {
+    if let Some(residency) = self.resident.get_mut(region) {
+      residency.dirty = true;
+    }
+  }
+
+  /// Write back and drop a region, if it's resident.
+  pub fn evict(&mut self, region: &bounds::T) -> Result<(), save::Error> {
+    let residency =
+      match self.resident.get(region) {
+        None => return Ok(()),
+        Some(residency) => *residency,
+      };
+
+    debug!("evicting {:?}", region);
+    if residency.dirty {
+      self.save(region)?;
+    }
+    if let Some(node) = self.tree.get_mut_pointer(region) {
+      *node = tree::Node::empty();
+    }
+    self.resident.remove(region);
+    Ok(())
+  }
+
+  /// Evict least recently used regions until no more than `capacity` are resident.
+  pub fn shrink(&mut self) -> Result<(), save::Error> {
+    while self.resident.len() > self.capacity {
+      let lru =
+        self.resident.iter()
+        .min_by_key(|&(_, residency)| residency.last_used)
+        .map(|(region, _)| *region)
+        .unwrap();
+      self.evict(&lru)?;
+    }
+    Ok(())
+  }
+
+  /// Write back every modified region, keeping them resident.
+  pub fn flush(&mut self) -> Result<(), save::Error> {
+    let dirty: Vec<bounds::T> =
+      self.resident.iter()
+      .filter(|&(_, residency)| residency.dirty)
+      .map(|(region, _)| *region)
+      .collect();
+    for region in &dirty {
+      self.save(region)?;
+      self.resident.get_mut(region).unwrap().dirty = false;
+    }
+    Ok(())
+  }
+
+  /// Find a voxel, faulting in its region if necessary.
+  pub fn get(&mut self, voxel: &bounds::T) -> Result<Option<&Voxel>, save::Error> {
+    if let Some(region) = self.region_of(voxel) {
+      self.fault_in(&region)?;
+      self.shrink()?;
+    }
+    Ok(self.tree.get(voxel))
+  }
+
+  /// Find a voxel to modify, faulting in its region if necessary and marking it dirty.
+  pub fn get_mut(&mut self, voxel: &bounds::T) -> Result<Option<&mut Voxel>, save::Error> {
+    if let Some(region) = self.region_of(voxel) {
+      self.fault_in(&region)?;
+      self.mark_dirty(&region);
+      self.shrink()?;
+    }
+    Ok(self.tree.get_mut(voxel))
+  }
+
+  /// Find a voxel to modify, creating it if it doesn't exist.
+  /// Its region is faulted in if necessary and marked dirty.
+  pub fn get_mut_or_create(&mut self, voxel: &bounds::T) -> Result<&mut tree::Node<Voxel>, save::Error> {
+    if let Some(region) = self.region_of(voxel) {
+      self.fault_in(&region)?;
+      self.mark_dirty(&region);
+      self.shrink()?;
+    }
+    Ok(self.tree.get_mut_or_create(voxel))
+  }
+
+  /// Apply a brush, faulting in the regions it touches one at a time. Regions are only marked
+  /// dirty if the brush updates a voxel in them.
+  /// Only regions are stored, so voxels larger than a region aren't generated, and the brush
+  /// never leaves its result in one.
+  pub fn brush<Material, Mosaic, Generate, OnVoxelUpdate>(
+    &mut self,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &mut Generate,
+    on_voxel_update: &mut OnVoxelUpdate,
+  ) -> Result<(), save::Error> where
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material>,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+    OnVoxelUpdate: FnMut(&Voxel, &::bounds::T),
+  {
+    let lg = self.region_lg_size;
+    let low = brush.bounds.min();
+    let high = brush.bounds.max();
+    for x in (low.x >> lg) .. ((high.x - 1) >> lg) + 1 {
+    for y in (low.y >> lg) .. ((high.y - 1) >> lg) + 1 {
+    for z in (low.z >> lg) .. ((high.z - 1) >> lg) + 1 {
+      let region = bounds::new(x, y, z, lg);
+      self.fault_in(&region)?;
+
+      let region_low = Point3::new(x << lg, y << lg, z << lg);
+      let region_high = Point3::new((x + 1) << lg, (y + 1) << lg, (z + 1) << lg);
+      let clipped =
+        brush::T {
+          bounds: brush::Bounds::new(
+            Point3::new(cmp::max(low.x, region_low.x), cmp::max(low.y, region_low.y), cmp::max(low.z, region_low.z)),
+            Point3::new(cmp::min(high.x, region_high.x), cmp::min(high.y, region_high.y), cmp::min(high.z, region_high.z)),
+          ),
+          mosaic: &brush.mosaic,
+          min_lg_size: brush.min_lg_size,
+          strength: brush.strength,
+          falloff: brush.falloff.clone(),
+        };
+      // Voxels larger than a region are never generated, so their nodes never have data, and
+      // brushes always descend through them to the regions.
+      let mut generate = |voxel: &bounds::T| {
+        if voxel.lg_size <= lg && region.contains(voxel) {
+          generate(voxel)
+        } else {
+          None
+        }
+      };
+      let mut updated = false;
+      self.tree.brush(&clipped, mode, &mut generate, &mut |voxel, bounds| {
+        updated = true;
+        on_voxel_update(voxel, bounds)
+      });
+      if updated {
+        self.mark_dirty(&region);
+      }
+      self.shrink()?;
+    }}}
+    Ok(())
+  }
+
+  /// Cast a ray through the regions it hits, nearest first, faulting them in one at a time
+  /// and stopping at the first hit. Voxels larger than a region aren't hit.
+  pub fn cast_ray<Act, R>(&mut self, ray: &Ray3<f32>, act: &mut Act) -> Result<Option<R>, save::Error>
+    where Act: FnMut(bounds::T, &Voxel) -> Option<R>
+  {
+    self.stored()?;
+    let (low, high) =
+      match self.extent {
+        None => return Ok(None),
+        Some(extent) => extent,
+      };
+
+    // Walk the regions along the ray (a 3D DDA), from where it enters the stored extent.
+    let lg = self.region_lg_size;
+    let size = (1 << lg) as f32;
+    let t =
+      match bounds::ray_entry(
+        &Point3::new(low.x as f32 * size, low.y as f32 * size, low.z as f32 * size),
+        &Point3::new((high.x + 1) as f32 * size, (high.y + 1) as f32 * size, (high.z + 1) as f32 * size),
+        ray,
+      ) {
+        None => return Ok(None),
+        Some(t) => t,
+      };
+    let entry = ray.origin + ray.direction * t;
+    let mut cell = [0; 3];
+    let mut next = [f32::INFINITY; 3];
+    let mut delta = [f32::INFINITY; 3];
+    let mut step = [0; 3];
+    for d in 0..3 {
+      let c = ((entry[d] / size).floor() as i32).max(low[d]).min(high[d]);
+      cell[d] = c;
+      if ray.direction[d] > 0.0 {
+        step[d] = 1;
+        next[d] = t + ((c + 1) as f32 * size - entry[d]) / ray.direction[d];
+        delta[d] = size / ray.direction[d];
+      } else if ray.direction[d] < 0.0 {
+        step[d] = -1;
+        next[d] = t + (c as f32 * size - entry[d]) / ray.direction[d];
+        delta[d] = -size / ray.direction[d];
+      }
+    }
+
+    while (0..3).all(|d| low[d] <= cell[d] && cell[d] <= high[d]) {
+      let region = bounds::new(cell[0], cell[1], cell[2], lg);
+      let known = self.resident.contains_key(&region) || self.stored()?.contains(&region);
+      if known {
+        self.fault_in(&region)?;
+        self.shrink()?;
+        let hit =
+          self.tree.cast_ray(ray, &mut |bounds, voxel| {
+            if region.contains(&bounds) {
+              act(bounds, voxel)
+            } else {
+              None
+            }
+          });
+        if hit.is_some() {
+          return Ok(hit)
+        }
+      }
+
+      let d = if next[0] <= next[1] && next[0] <= next[2] { 0 } else if next[1] <= next[2] { 1 } else { 2 };
+      if step[d] == 0 {
+        break
+      }
+      cell[d] += step[d];
+      next[d] += delta[d];
+    }
+    Ok(None)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std;
+
+  use cgmath::{Point3, Vector3};
+
+  use super::*;
+  use bounds;
+  use brush;
+  use field;
+  use impls::surface_vertex;
+  use mosaic;
+  use tree;
+
+  #[test]
+  fn evicted_regions_are_reloaded() {
+    let dir = std::env::temp_dir().join(format!("voxel-data-paged-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut world: T<i32, directory::T> = new(directory::new(&dir).unwrap(), 2, 1);
+    *world.get_mut_or_create(&bounds::new(1, 2, 3, 0)).unwrap() = tree::Node::leaf(Some(1));
+    *world.get_mut_or_create(&bounds::new(9, 2, 3, 0)).unwrap() = tree::Node::leaf(Some(2));
+
+    assert_eq!(world.resident(), vec!(bounds::new(2, 0, 0, 2)));
+    assert_eq!(world.tree().get(&bounds::new(1, 2, 3, 0)), None);
+    assert!(world.store().path_of(&bounds::new(0, 0, 0, 2)).exists());
+
+    assert_eq!(world.get(&bounds::new(1, 2, 3, 0)).unwrap(), Some(&1));
+    assert_eq!(world.resident(), vec!(bounds::new(0, 0, 0, 2)));
+    assert_eq!(world.get(&bounds::new(9, 2, 3, 0)).unwrap(), Some(&2));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn rays_hit_the_nearest_region_first() {
+    let dir = std::env::temp_dir().join(format!("voxel-data-paged-ray-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut world: T<i32, directory::T> = new(directory::new(&dir).unwrap(), 2, 1);
+    *world.get_mut_or_create(&bounds::new(1, 0, 0, 0)).unwrap() = tree::Node::leaf(Some(1));
+    *world.get_mut_or_create(&bounds::new(9, 0, 0, 0)).unwrap() = tree::Node::leaf(Some(2));
+    world.flush().unwrap();
+
+    let ray = Ray3::new(Point3::new(12.5, 0.5, 0.5), Vector3::new(-1.0, 0.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))).unwrap(), Some((bounds::new(9, 0, 0, 0), 2)));
+    let ray = Ray3::new(Point3::new(-3.5, 0.5, 0.5), Vector3::new(1.0, 0.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))).unwrap(), Some((bounds::new(1, 0, 0, 0), 1)));
+    assert_eq!(world.resident().len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn rays_walk_regions_from_inside_the_store() {
+    let dir = std::env::temp_dir().join(format!("voxel-data-paged-walk-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut world: T<i32, directory::T> = new(directory::new(&dir).unwrap(), 2, 1);
+    *world.get_mut_or_create(&bounds::new(-6, -6, 2, 0)).unwrap() = tree::Node::leaf(Some(1));
+    *world.get_mut_or_create(&bounds::new(5, 5, 2, 0)).unwrap() = tree::Node::leaf(Some(2));
+    *world.get_mut_or_create(&bounds::new(9, 9, 2, 0)).unwrap() = tree::Node::leaf(Some(3));
+    world.flush().unwrap();
+
+    // Diagonally, through several regions that aren't stored, starting between the voxels.
+    let ray = Ray3::new(Point3::new(0.5, 0.6, 2.5), Vector3::new(1.0, 1.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))).unwrap(), Some((bounds::new(5, 5, 2, 0), 2)));
+    let ray = Ray3::new(Point3::new(0.5, 0.6, 2.5), Vector3::new(-1.0, -1.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))).unwrap(), Some((bounds::new(-6, -6, 2, 0), 1)));
+    let ray = Ray3::new(Point3::new(0.5, 0.6, 2.5), Vector3::new(1.0, -1.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))).unwrap(), None);
+    let ray = Ray3::new(Point3::new(0.5, 0.5, 20.5), Vector3::new(1.0, 1.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))).unwrap(), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn brushes_only_dirty_updated_regions() {
+    let dir = std::env::temp_dir().join(format!("voxel-data-paged-dirty-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let brush =
+      brush::new(
+        brush::Bounds::new(Point3::new(0, 0, 0), Point3::new(12, 4, 4)),
+        mosaic::solid::T { field: field::sphere::T { radius: 60.0 }, material: 1u8 },
+        0,
+      );
+    let mut world: T<surface_vertex::T<u8>, directory::T> = new(directory::new(&dir).unwrap(), 2, 1);
+    // Only the middle region has anything to brush.
+    let mut generate = |voxel: &bounds::T| {
+      if bounds::new(1, 0, 0, 2).contains(voxel) { Some(surface_vertex::T::Volume(0)) } else { None }
+    };
+    world.brush(&brush, &brush::Mode::Add, &mut generate, &mut |_, _| {}).unwrap();
+    // Regions are evicted as the brush goes, so only the last one is still resident.
+    assert_eq!(world.resident(), vec!(bounds::new(2, 0, 0, 2)));
+    world.flush().unwrap();
+
+    assert!(!world.store().path_of(&bounds::new(0, 0, 0, 2)).exists());
+    assert!(world.store().path_of(&bounds::new(1, 0, 0, 2)).exists());
+    assert!(!world.store().path_of(&bounds::new(2, 0, 0, 2)).exists());
+    assert_eq!(world.get(&bounds::new(1, 0, 0, 2)).unwrap().cloned(), Some(surface_vertex::T::Volume(1)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn homogeneous_brushes_are_reloaded() {
+    let dir = std::env::temp_dir().join(format!("voxel-data-paged-homogeneous-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // The brush is homogeneous over voxels larger than a region.
+    let brush =
+      brush::new(
+        brush::Bounds::new(Point3::new(0, 0, 0), Point3::new(8, 8, 8)),
+        mosaic::solid::T { field: field::sphere::T { radius: 60.0 }, material: 1u8 },
+        0,
+      );
+    let mut world: T<surface_vertex::T<u8>, directory::T> = new(directory::new(&dir).unwrap(), 2, 1);
+    world.brush(&brush, &brush::Mode::Add, &mut |_| Some(surface_vertex::T::Volume(0)), &mut |_, _| {}).unwrap();
+    let brushed = world.get(&bounds::new(1, 1, 1, 2)).unwrap().cloned();
+    assert_eq!(brushed, Some(surface_vertex::T::Volume(1)));
+    world.flush().unwrap();
+
+    let mut world: T<surface_vertex::T<u8>, directory::T> = new(directory::new(&dir).unwrap(), 2, 1);
+    assert_eq!(world.get(&bounds::new(1, 1, 1, 2)).unwrap().cloned(), brushed);
+    assert_eq!(world.get(&bounds::new(0, 0, 0, 2)).unwrap().cloned(), brushed);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,241 @@
+//! A voxel tree that can be read and modified from several threads at once.
+//!
+//! Space is divided into regions of a fixed `lg_size`, each kept in its own `tree::T` behind its
+//! own lock, so operations on different regions don't contend. Voxels larger than a region
+//! aren't stored.
+
+use cgmath::Point3;
+use collision::{Aabb, Ray3};
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+
+use bounds;
+use brush;
+use mosaic;
+use tree;
+
+type Region<Voxel> = Arc<RwLock<tree::T<Voxel>>>;
+
+#[allow(missing_docs)]
+pub struct T<Voxel> {
+  region_lg_size: i16,
+  regions: RwLock<HashMap<bounds::T, Region<Voxel>>>,
+}
+
+/// Create an empty sharded tree with regions of size `2^region_lg_size`.
+pub fn new<Voxel>(region_lg_size: i16) -> T<Voxel> {
+  assert!(region_lg_size >= 0);
+  T {
+    region_lg_size: region_lg_size,
+    regions: RwLock::new(HashMap::new()),
+  }
+}
+
+// The order regions are locked in, when more than one is locked at once.
+fn lock_order(region: &bounds::T) -> (i32, i32, i32) {
+  (region.x, region.y, region.z)
+}
+
+impl<Voxel> T<Voxel> {
+  /// The region containing a voxel, or `None` if the voxel is larger than a region.
+  pub fn region_of(&self, voxel: &bounds::T) -> Option<bounds::T> {
+    if voxel.lg_size > self.region_lg_size {
+      return None
+    }
+    let shift = self.region_lg_size - voxel.lg_size;
+    Some(bounds::new(voxel.x >> shift, voxel.y >> shift, voxel.z >> shift, self.region_lg_size))
+  }
+
+  /// Every region that has been created.
+  pub fn regions(&self) -> Vec<bounds::T> {
+    self.regions.read().unwrap().keys().cloned().collect()
+  }
+
+  fn find(&self, region: &bounds::T) -> Option<Region<Voxel>> {
+    self.regions.read().unwrap().get(region).cloned()
+  }
+
+  fn find_or_create(&self, region: &bounds::T) -> Region<Voxel> {
+    if let Some(tree) = self.find(region) {
+      return tree
+    }
+    let mut regions = self.regions.write().unwrap();
+    regions.entry(*region).or_insert_with(|| {
+      let mut tree = tree::new();
+      tree.grow_to_hold(region);
+      Arc::new(RwLock::new(tree))
+    }).clone()
+  }
+
+  /// Find a voxel.
+  pub fn get(&self, voxel: &bounds::T) -> Option<Voxel> where Voxel: Clone {
+    let tree = self.find(&self.region_of(voxel)?)?;
+    let tree = tree.read().unwrap();
+    tree.get(voxel).cloned()
+  }
+
+  /// Set the data of a voxel, creating its region if necessary.
+  /// Panics if the voxel is larger than a region.
+  pub fn set(&self, voxel: &bounds::T, data: Option<Voxel>) {
+    let region = self.region_of(voxel).expect("voxel is larger than a region");
+    let tree = self.find_or_create(&region);
+    let mut tree = tree.write().unwrap();
+    tree.get_mut_or_create(voxel).data = data;
+  }
+
+  /// Apply a brush. Every region it touches is locked for the whole operation, so other threads
+  /// see either none of the brush or all of it.
+  pub fn brush<Material, Mosaic, Generate, OnVoxelUpdate>(
+    &self,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &mut Generate,
+    on_voxel_update: &mut OnVoxelUpdate,
+  ) where
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material>,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+    OnVoxelUpdate: FnMut(&Voxel, &::bounds::T),
+  {
+    let lg = self.region_lg_size;
+    let low = brush.bounds.min();
+    let high = brush.bounds.max();
+    let mut regions = Vec::new();
+    for x in (low.x >> lg) .. ((high.x - 1) >> lg) + 1 {
+    for y in (low.y >> lg) .. ((high.y - 1) >> lg) + 1 {
+    for z in (low.z >> lg) .. ((high.z - 1) >> lg) + 1 {
+      let region = bounds::new(x, y, z, lg);
+      regions.push((region, self.find_or_create(&region)));
+    }}}
+
+    // Lock in a consistent order, so overlapping brushes can't deadlock.
+    regions.sort_by_key(|(region, _)| lock_order(region));
+    let mut locked: Vec<(bounds::T, RwLockWriteGuard<tree::T<Voxel>>)> =
+      regions.iter().map(|&(region, ref tree)| (region, tree.write().unwrap())).collect();
+
+    for &mut (region, ref mut tree) in &mut locked {
+      let region_low = Point3::new(region.x << lg, region.y << lg, region.z << lg);
+      let region_high = Point3::new((region.x + 1) << lg, (region.y + 1) << lg, (region.z + 1) << lg);
+      let clipped =
+        brush::T {
+          bounds: brush::Bounds::new(
+            Point3::new(cmp::max(low.x, region_low.x), cmp::max(low.y, region_low.y), cmp::max(low.z, region_low.z)),
+            Point3::new(cmp::min(high.x, region_high.x), cmp::min(high.y, region_high.y), cmp::min(high.z, region_high.z)),
+          ),
+          mosaic: &brush.mosaic,
+          min_lg_size: brush.min_lg_size,
+          strength: brush.strength,
+          falloff: brush.falloff.clone(),
+        };
+      // Don't generate voxels that belong to other regions, or that are larger than a region.
+      let mut generate = |voxel: &bounds::T| {
+        if voxel.lg_size <= lg && region.contains(voxel) {
+          generate(voxel)
+        } else {
+          None
+        }
+      };
+      tree.brush(&clipped, mode, &mut generate, on_voxel_update);
+    }
+  }
+
+  /// Cast a ray through the regions it hits, nearest first.
+  pub fn cast_ray<Act, R>(&self, ray: &Ray3<f32>, act: &mut Act) -> Option<R>
+    where Act: FnMut(bounds::T, &Voxel) -> Option<R>
+  {
+    let mut regions: Vec<(f32, Region<Voxel>)> =
+      self.regions.read().unwrap().iter()
+      .filter_map(|(region, tree)| region.ray_entry(ray).map(|toi| (toi, tree.clone())))
+      .filter(|&(toi, _)| !toi.is_nan())
+      .collect();
+    // Regions don't overlap, so the order they're entered in is the order they're passed through.
+    regions.sort_by(|&(t1, _), &(t2, _)| t1.partial_cmp(&t2).unwrap_or(cmp::Ordering::Equal));
+
+    for (_, tree) in &regions {
+      let tree = tree.read().unwrap();
+      if let Some(r) = tree.cast_ray(ray, act) {
+        return Some(r)
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Point3, Vector3};
+  use collision::Ray3;
+  use std::sync::Arc;
+  use std::thread;
+
+  use bounds;
+  use brush;
+  use field;
+  use mosaic;
+
+  struct Fill;
+
+  impl field::T for Fill {
+    fn density(&self, _: &Point3<f32>) -> f32 {
+      1.0
+    }
+
+    fn normal(&self, _: &Point3<f32>) -> Vector3<f32> {
+      Vector3::new(0.0, 0.0, 0.0)
+    }
+  }
+
+  impl mosaic::T<()> for Fill {
+    fn material(&self, _: &Point3<f32>) -> Option<()> {
+      None
+    }
+  }
+
+  fn fill(low: Point3<i32>, high: Point3<i32>) -> brush::T<Fill> {
+    brush::new(brush::Bounds::new(low, high), Fill, 0)
+  }
+
+  #[test]
+  fn brushes_span_regions() {
+    let world: super::T<i32> = super::new(2);
+    world.set(&bounds::new(3, 0, 0, 0), Some(1));
+    world.set(&bounds::new(4, 0, 0, 0), Some(2));
+    assert_eq!(world.regions().len(), 2);
+
+    let mut generated = Vec::new();
+    world.brush(
+      &fill(Point3::new(3, 0, 0), Point3::new(5, 1, 1)),
+      &brush::Mode::Add,
+      &mut |b| { generated.push(*b); if b.lg_size == 0 { Some(0) } else { None } },
+      &mut |_, _| {},
+    );
+    assert_eq!(world.get(&bounds::new(3, 0, 0, 0)), Some(999));
+    assert_eq!(world.get(&bounds::new(4, 0, 0, 0)), Some(999));
+    assert!(generated.iter().all(|b| b.lg_size <= 2));
+
+    let ray = Ray3::new(Point3::new(8.5, 0.5, 0.5), Vector3::new(-1.0, 0.0, 0.0));
+    assert_eq!(world.cast_ray(&ray, &mut |b, &v| Some((b, v))), Some((bounds::new(5, 0, 0, 0), 999)));
+  }
+
+  #[test]
+  fn concurrent_brushes_and_rays() {
+    let world: Arc<super::T<i32>> = Arc::new(super::new(2));
+    let threads: Vec<_> = (0..4).map(|i| {
+      let world = world.clone();
+      thread::spawn(move || {
+        for j in 0..20 {
+          // Alternate between brushes that start in different regions.
+          let x = if (i + j) % 2 == 0 { -6 } else { 2 };
+          world.brush(&fill(Point3::new(x, -2, -2), Point3::new(x + 5, 2, 2)), &brush::Mode::Add, &mut |_| Some(0), &mut |_, _| {});
+          let ray = Ray3::new(Point3::new(-10.0, 0.5, 0.5), Vector3::new(1.0, 0.0, 0.0));
+          world.cast_ray(&ray, &mut |_, _| Some(()));
+        }
+      })
+    }).collect();
+    for thread in threads {
+      thread.join().unwrap();
+    }
+    assert_eq!(world.get(&bounds::new(-6, 0, 0, 0)), Some(999));
+  }
+}
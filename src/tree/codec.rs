@@ -0,0 +1,290 @@
+//! A compact binary encoding of tree nodes.
+//!
+//! A group of sibling nodes is written as a byte with a bit set for each node that has a voxel,
+//! a byte with a bit set for each node that has branches, the voxels that are present, and then
+//! the groups of children of each node that has branches. Empty nodes take no space at all.
+//!
+//! With `Compression::RunLength`, the structure of the whole subtree is written first,
+//! followed by the voxels in the same order, as runs of identical voxels.
+
+use std::io;
+
+use tree;
+use tree::save;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum Compression {
+  None,
+  /// Collapse runs of identical voxels.
+  RunLength,
+}
+
+impl Compression {
+  fn tag(self) -> u8 {
+    match self {
+      Compression::None => 0,
+      Compression::RunLength => 1,
+    }
+  }
+
+  fn of_tag(tag: u8) -> io::Result<Compression> {
+    match tag {
+      0 => Ok(Compression::None),
+      1 => Ok(Compression::RunLength),
+      tag => Err(invalid_data(format!("bad compression tag {}", tag))),
+    }
+  }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Write a LEB128-encoded integer.
+pub fn write_varint<W: io::Write>(w: &mut W, mut x: u64) -> io::Result<()> {
+  loop {
+    let byte = (x & 0x7f) as u8;
+    x >>= 7;
+    if x == 0 {
+      return w.write_all(&[byte])
+    }
+    w.write_all(&[byte | 0x80])?;
+  }
+}
+
+/// Read a LEB128-encoded integer.
+pub fn read_varint<R: io::Read>(r: &mut R) -> io::Result<u64> {
+  let mut x = 0;
+  let mut shift = 0;
+  loop {
+    let byte = save::read_u8(r)?;
+    if shift >= 64 {
+      return Err(invalid_data("varint is too long".to_string()))
+    }
+    x |= ((byte & 0x7f) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(x)
+    }
+    shift += 7;
+  }
+}
+
+fn masks<V>(nodes: &[tree::Node<V>]) -> (u8, u8) {
+  let mut data_mask = 0;
+  let mut branch_mask = 0;
+  for (i, node) in nodes.iter().enumerate() {
+    if node.data.is_some() {
+      data_mask |= 1 << i;
+    }
+    if let tree::Inner::Branches(_) = node.next {
+      branch_mask |= 1 << i;
+    }
+  }
+  (data_mask, branch_mask)
+}
+
+fn children<V>(node: &tree::Node<V>) -> Option<&[tree::Node<V>; 8]> {
+  match node.next {
+    tree::Inner::Empty => None,
+    tree::Inner::Branches(ref branches) => Some(branches.as_flat_array()),
+  }
+}
+
+fn write_group<W, V>(w: &mut W, nodes: &[tree::Node<V>]) -> io::Result<()> where
+  W: io::Write,
+  V: save::Voxel,
+{
+  let (data_mask, branch_mask) = masks(nodes);
+  w.write_all(&[data_mask, branch_mask])?;
+  for voxel in nodes.iter().filter_map(|node| node.data.as_ref()) {
+    voxel.write(w)?;
+  }
+  for children in nodes.iter().filter_map(children) {
+    write_group(w, children)?;
+  }
+  Ok(())
+}
+
+fn write_structure<W, V>(w: &mut W, nodes: &[tree::Node<V>]) -> io::Result<()> where
+  W: io::Write,
+{
+  let (data_mask, branch_mask) = masks(nodes);
+  w.write_all(&[data_mask, branch_mask])?;
+  for children in nodes.iter().filter_map(children) {
+    write_structure(w, children)?;
+  }
+  Ok(())
+}
+
+struct Runs<'a, W: 'a, V: 'a> {
+  w: &'a mut W,
+  run: Option<(&'a V, u64)>,
+}
+
+impl<'a, W, V> Runs<'a, W, V> where
+  W: io::Write,
+  V: save::Voxel + PartialEq,
+{
+  fn push(&mut self, voxel: &'a V) -> io::Result<()> {
+    if let Some((prev, ref mut count)) = self.run {
+      if prev == voxel {
+        *count += 1;
+        return Ok(())
+      }
+    }
+    self.flush()?;
+    self.run = Some((voxel, 1));
+    Ok(())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if let Some((voxel, count)) = self.run.take() {
+      write_varint(self.w, count)?;
+      voxel.write(self.w)?;
+    }
+    Ok(())
+  }
+
+  // Visit voxels in the same order `write_group` does.
+  fn push_group(&mut self, nodes: &'a [tree::Node<V>]) -> io::Result<()> {
+    for voxel in nodes.iter().filter_map(|node| node.data.as_ref()) {
+      self.push(voxel)?;
+    }
+    for children in nodes.iter().filter_map(children) {
+      self.push_group(children)?;
+    }
+    Ok(())
+  }
+}
+
+/// Encode a group of up to 8 sibling nodes, and everything below them.
+pub fn write<W, V>(w: &mut W, nodes: &[tree::Node<V>], compression: Compression) -> io::Result<()> where
+  W: io::Write,
+  V: save::Voxel + PartialEq,
+{
+  assert!(nodes.len() <= 8);
+  w.write_all(&[compression.tag()])?;
+  match compression {
+    Compression::None => write_group(w, nodes),
+    Compression::RunLength => {
+      write_structure(w, nodes)?;
+      let mut runs = Runs { w: w, run: None };
+      runs.push_group(nodes)?;
+      runs.flush()
+    },
+  }
+}
+
+fn too_deep() -> io::Error {
+  invalid_data("tree is too deep".to_string())
+}
+
+fn read_group<R, V>(r: &mut R, nodes: &mut [tree::Node<V>], depth_left: u32) -> io::Result<()> where
+  R: io::Read,
+  V: save::Voxel,
+{
+  let data_mask = save::read_u8(r)?;
+  let branch_mask = save::read_u8(r)?;
+  for (i, node) in nodes.iter_mut().enumerate() {
+    if data_mask & (1 << i) != 0 {
+      node.data = Some(V::read(r)?);
+    }
+  }
+  for (i, node) in nodes.iter_mut().enumerate() {
+    if branch_mask & (1 << i) != 0 {
+      if depth_left == 0 {
+        return Err(too_deep())
+      }
+      read_group(r, node.force_branches().as_flat_array_mut(), depth_left - 1)?;
+    }
+  }
+  Ok(())
+}
+
+// Build the branches of the subtree, and return the data masks of every group, in order.
+fn read_structure<R, V>(
+  r: &mut R,
+  nodes: &mut [tree::Node<V>],
+  depth_left: u32,
+  data_masks: &mut Vec<u8>,
+) -> io::Result<()> where
+  R: io::Read,
+{
+  let data_mask = save::read_u8(r)?;
+  let branch_mask = save::read_u8(r)?;
+  data_masks.push(data_mask);
+  for (i, node) in nodes.iter_mut().enumerate() {
+    if branch_mask & (1 << i) != 0 {
+      if depth_left == 0 {
+        return Err(too_deep())
+      }
+      read_structure(r, node.force_branches().as_flat_array_mut(), depth_left - 1, data_masks)?;
+    }
+  }
+  Ok(())
+}
+
+struct RunReader<'a, R: 'a, V> {
+  r: &'a mut R,
+  data_masks: ::std::vec::IntoIter<u8>,
+  run: Option<(V, u64)>,
+}
+
+impl<'a, R, V> RunReader<'a, R, V> where
+  R: io::Read,
+  V: save::Voxel + Clone,
+{
+  fn next(&mut self) -> io::Result<V> {
+    if let Some((ref voxel, ref mut count)) = self.run {
+      if *count > 0 {
+        *count -= 1;
+        return Ok(voxel.clone())
+      }
+    }
+    let count = read_varint(self.r)?;
+    if count == 0 {
+      return Err(invalid_data("empty voxel run".to_string()))
+    }
+    let voxel = V::read(self.r)?;
+    self.run = Some((voxel.clone(), count - 1));
+    Ok(voxel)
+  }
+
+  fn fill_group(&mut self, nodes: &mut [tree::Node<V>]) -> io::Result<()> {
+    let data_mask = self.data_masks.next().unwrap();
+    for (i, node) in nodes.iter_mut().enumerate() {
+      if data_mask & (1 << i) != 0 {
+        node.data = Some(self.next()?);
+      }
+    }
+    for node in nodes.iter_mut() {
+      if let tree::Inner::Branches(ref mut branches) = node.next {
+        self.fill_group(branches.as_flat_array_mut())?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Decode a group of sibling nodes encoded by `write` into `nodes`, which should be empty.
+/// Data nested more than `max_depth` levels below `nodes` is rejected.
+pub fn read<R, V>(r: &mut R, nodes: &mut [tree::Node<V>], max_depth: u32) -> io::Result<()> where
+  R: io::Read,
+  V: save::Voxel + Clone,
+{
+  assert!(nodes.len() <= 8);
+  match Compression::of_tag(save::read_u8(r)?)? {
+    Compression::None => read_group(r, nodes, max_depth),
+    Compression::RunLength => {
+      let mut data_masks = Vec::new();
+      read_structure(r, nodes, max_depth, &mut data_masks)?;
+      let mut runs = RunReader { r: r, data_masks: data_masks.into_iter(), run: None };
+      runs.fill_group(nodes)?;
+      match runs.run {
+        Some((_, 0)) | None => Ok(()),
+        Some(_) => Err(invalid_data("voxel run is longer than the tree".to_string())),
+      }
+    },
+  }
+}
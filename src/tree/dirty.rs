@@ -0,0 +1,56 @@
+//! Tracking which chunks of space need to be remeshed after voxels change.
+//!
+//! A chunk's mesh joins the vertices of its voxels to the vertices of the voxels just past its
+//! high faces, so a voxel on a chunk's low face dirties the neighbouring chunks below it too.
+
+use std::collections::HashSet;
+
+use bounds;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct T {
+  /// The lg_size of the chunks.
+  pub chunk_lg_size: i16,
+  /// Every chunk that has been marked dirty.
+  pub chunks: HashSet<bounds::T>,
+}
+
+#[allow(missing_docs)]
+pub fn new(chunk_lg_size: i16) -> T {
+  T {
+    chunk_lg_size: chunk_lg_size,
+    chunks: HashSet::new(),
+  }
+}
+
+impl T {
+  // The range of chunks along one axis whose meshes depend on a voxel at `x`.
+  fn range(&self, x: i32, lg_size: i16) -> (i32, i32) {
+    if lg_size >= self.chunk_lg_size {
+      let shift = lg_size - self.chunk_lg_size;
+      (x << shift, ((x + 1) << shift) - 1)
+    } else {
+      let shift = self.chunk_lg_size - lg_size;
+      (x >> shift, x >> shift)
+    }
+  }
+
+  /// Mark every chunk whose mesh depends on a voxel.
+  pub fn mark(&mut self, voxel: &bounds::T) {
+    let low_face = |x: i32| {
+      voxel.lg_size >= self.chunk_lg_size ||
+      x & ((1 << (self.chunk_lg_size - voxel.lg_size)) - 1) == 0
+    };
+    let axis = |x: i32| {
+      let (low, high) = self.range(x, voxel.lg_size);
+      (if low_face(x) { low - 1 } else { low }, high)
+    };
+    let (x, y, z) = (axis(voxel.x), axis(voxel.y), axis(voxel.z));
+    for cx in x.0 .. x.1 + 1 {
+    for cy in y.0 .. y.1 + 1 {
+    for cz in z.0 .. z.1 + 1 {
+      self.chunks.insert(bounds::new(cx, cy, cz, self.chunk_lg_size));
+    }}}
+  }
+}
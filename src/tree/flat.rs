@@ -0,0 +1,345 @@
+//! A flat, pointer-free layout of a voxel tree that can be used in place, e.g. from an mmap.
+//!
+//! After a header like a save's (starting with `MAGIC`) and the tree's `lg_size`, the file is
+//! a sequence of records, children before their parents:
+//!
+//!  * A voxel is stored as encoded by `save::Voxel`.
+//!  * A group of eight sibling nodes is stored as a byte with a bit set for each node that has
+//!    a voxel, a byte with a bit set for each node that has branches, and then a little-endian
+//!    `u32` offset for each set bit: first the offsets of the children's own groups, then the
+//!    offsets of their voxels.
+//!
+//! The file ends with the offset of the top-level group. All offsets are from the start of the
+//! file, and a group's offsets always point backwards, so a corrupt file can't cause a loop.
+//! Corrupt data is treated as empty, rather than causing a panic.
+
+use collision::Ray3;
+use std::io;
+use std::marker::PhantomData;
+
+use bounds;
+use tree;
+use tree::raycast;
+use tree::save;
+use tree::validate;
+
+/// The bytes every flat tree starts with.
+pub const MAGIC: [u8; 4] = *b"SVOf";
+/// The version of the layout written by `write`.
+pub const VERSION: u32 = 1;
+
+struct Counting<'a, W: 'a> {
+  w: &'a mut W,
+  position: u64,
+}
+
+impl<'a, W> io::Write for Counting<'a, W> where W: io::Write {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.w.write(buf)?;
+    self.position += n as u64;
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.w.flush()
+  }
+}
+
+impl<'a, W> Counting<'a, W> where W: io::Write {
+  fn offset(&self) -> io::Result<u32> {
+    if self.position > u32::MAX as u64 {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "flat tree is larger than 4GiB"))
+    }
+    Ok(self.position as u32)
+  }
+}
+
+// Write a group and everything below it, returning the offset of the group's record.
+fn write_group<W, V>(w: &mut Counting<W>, nodes: &[tree::Node<V>; 8]) -> io::Result<u32> where
+  W: io::Write,
+  V: save::Voxel,
+{
+  let mut data_mask = 0;
+  let mut branch_mask = 0;
+  let mut branch_offsets = Vec::new();
+  let mut data_offsets = Vec::new();
+  for (i, node) in nodes.iter().enumerate() {
+    if let tree::Inner::Branches(ref branches) = node.next {
+      branch_mask |= 1 << i;
+      branch_offsets.push(write_group(w, branches.as_flat_array())?);
+    }
+  }
+  for (i, node) in nodes.iter().enumerate() {
+    if let Some(ref voxel) = node.data {
+      data_mask |= 1 << i;
+      data_offsets.push(w.offset()?);
+      voxel.write(w)?;
+    }
+  }
+
+  let offset = w.offset()?;
+  io::Write::write_all(w, &[data_mask, branch_mask])?;
+  for &child in branch_offsets.iter().chain(data_offsets.iter()) {
+    save::write_u32(w, child)?;
+  }
+  Ok(offset)
+}
+
+/// Write a tree in the flat layout.
+pub fn write<W, V>(w: &mut W, tree: &tree::T<V>) -> io::Result<()> where
+  W: io::Write,
+  V: save::Voxel,
+{
+  let mut w = Counting { w: w, position: 0 };
+  save::write_header_with_magic(&mut w, &MAGIC, &save::Header {
+    version: VERSION,
+    voxel_type: V::type_id(),
+  })?;
+  io::Write::write_all(&mut w, &[tree.lg_size])?;
+  let root = write_group(&mut w, tree.contents.as_flat_array())?;
+  save::write_u32(&mut w, root)
+}
+
+/// A read-only view of a tree in the flat layout.
+pub struct View<'a, V> {
+  bytes: &'a [u8],
+  lg_size: u8,
+  root: u32,
+  voxel: PhantomData<V>,
+}
+
+// Derived impls would needlessly require `V: Clone`.
+impl<'a, V> Clone for View<'a, V> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<'a, V> Copy for View<'a, V> {}
+
+/// View a flat tree. Only the header is checked; nothing else is read until it's needed.
+pub fn view<'a, V>(bytes: &'a [u8]) -> Result<View<'a, V>, save::Error> where V: save::Voxel {
+  let mut r = bytes;
+  let header = save::read_header_with_magic(&mut r, &MAGIC)?;
+  if header.version != VERSION || header.voxel_type != V::type_id() {
+    return Err(save::Error::Unsupported(header))
+  }
+
+  let lg_size = save::read_u8(&mut r)?;
+  if lg_size > validate::MAX_LG_SIZE || bytes.len() < 4 {
+    return Err(save::Error::Io(io::Error::new(io::ErrorKind::InvalidData, "bad flat tree")))
+  }
+  let root = save::read_u32(&mut &bytes[bytes.len() - 4 ..])?;
+  Ok(View {
+    bytes: bytes,
+    lg_size: lg_size,
+    root: root,
+    voxel: PhantomData,
+  })
+}
+
+/// A group of eight sibling nodes in a `View`.
+pub struct Branches<'a, V: 'a> {
+  view: View<'a, V>,
+  offset: u32,
+  // How far below the top-level group this is.
+  depth: u8,
+}
+
+impl<'a, V> Clone for Branches<'a, V> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<'a, V> Copy for Branches<'a, V> {}
+
+/// A node in a `View`.
+pub struct Node<'a, V: 'a> {
+  // The group this node is in, and which child of that group it is.
+  group: Branches<'a, V>,
+  index: usize,
+}
+
+impl<'a, V> Clone for Node<'a, V> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<'a, V> Copy for Node<'a, V> {}
+
+impl<'a, V> Branches<'a, V> {
+  fn masks(&self) -> (u8, u8) {
+    match self.view.bytes.get(self.offset as usize .. self.offset as usize + 2) {
+      None => (0, 0),
+      Some(masks) => (masks[0], masks[1]),
+    }
+  }
+
+  /// The child at a given position, as in `tree::Branches::as_array`.
+  pub fn child(&self, x: usize, y: usize, z: usize) -> Node<'a, V> {
+    Node {
+      group: *self,
+      index: (x << 2) | (y << 1) | z,
+    }
+  }
+}
+
+impl<'a, V> Node<'a, V> where V: save::Voxel {
+  // The `n`th offset stored in this node's group record.
+  fn offset(&self, n: u32) -> Option<u32> {
+    let at = self.group.offset as usize + 2 + 4 * n as usize;
+    let mut bytes = self.group.view.bytes.get(at .. at + 4)?;
+    let offset = save::read_u32(&mut bytes).ok()?;
+    // Offsets only ever point backwards.
+    if offset < self.group.offset {
+      Some(offset)
+    } else {
+      None
+    }
+  }
+
+  /// The voxel stored at this node, if any.
+  pub fn data(&self) -> Option<V> {
+    let (data_mask, branch_mask) = self.group.masks();
+    if data_mask & (1 << self.index) == 0 {
+      return None
+    }
+    let below = (1 << self.index) - 1;
+    let n = branch_mask.count_ones() + (data_mask & below).count_ones();
+    let offset = self.offset(n)?;
+    V::read(&mut &self.group.view.bytes[offset as usize ..]).ok()
+  }
+
+  /// The children of this node, if any.
+  pub fn branches(&self) -> Option<Branches<'a, V>> {
+    let (_, branch_mask) = self.group.masks();
+    if branch_mask & (1 << self.index) == 0 || self.group.depth >= validate::MAX_LG_SIZE {
+      return None
+    }
+    let below = (1 << self.index) - 1;
+    let offset = self.offset((branch_mask & below).count_ones())?;
+    Some(Branches {
+      view: self.group.view,
+      offset: offset,
+      depth: self.group.depth + 1,
+    })
+  }
+}
+
+impl<'a, V> raycast::Branches for Branches<'a, V> where V: save::Voxel {
+  type Node = Node<'a, V>;
+
+  fn child(self, coords: [usize; 3]) -> Node<'a, V> {
+    Branches::child(&self, coords[0], coords[1], coords[2])
+  }
+}
+
+impl<'a, V> raycast::Node for Node<'a, V> where V: save::Voxel {
+  type Branches = Branches<'a, V>;
+  type Voxel = V;
+
+  fn data(self) -> Option<V> {
+    Node::data(&self)
+  }
+
+  fn branches(self) -> Option<Branches<'a, V>> {
+    Node::branches(&self)
+  }
+}
+
+impl<'a, V> View<'a, V> where V: save::Voxel {
+  #[allow(missing_docs)]
+  pub fn lg_size(&self) -> u8 {
+    self.lg_size
+  }
+
+  /// The top-level group of nodes.
+  pub fn contents(&self) -> Branches<'a, V> {
+    Branches {
+      view: *self,
+      offset: self.root,
+      depth: 0,
+    }
+  }
+
+  /// Find the node at some bounds.
+  pub fn get_pointer(&self, voxel: &bounds::T) -> Option<Node<'a, V>> {
+    let lg_size = self.lg_size as i16;
+    if voxel.lg_size > lg_size || lg_size - voxel.lg_size > validate::MAX_LG_SIZE as i16 {
+      return None
+    }
+
+    let mut depth = lg_size - voxel.lg_size;
+    let top = |x: i32| x >> depth;
+    let (x, y, z) = (top(voxel.x), top(voxel.y), top(voxel.z));
+    let in_range = |x: i32| x == -1 || x == 0;
+    if !(in_range(x) && in_range(y) && in_range(z)) {
+      return None
+    }
+
+    let mut node = self.contents().child((x + 1) as usize, (y + 1) as usize, (z + 1) as usize);
+    while depth > 0 {
+      depth -= 1;
+      let bit = |x: i32| ((x >> depth) & 1) as usize;
+      node = node.branches()?.child(bit(voxel.x), bit(voxel.y), bit(voxel.z));
+    }
+    Some(node)
+  }
+
+  /// Find a voxel.
+  pub fn get(&self, voxel: &bounds::T) -> Option<V> {
+    self.get_pointer(voxel).and_then(|node| node.data())
+  }
+
+  /// Call `f` on every voxel inside `region`.
+  pub fn for_each_in<F>(&self, region: &bounds::T, f: &mut F) where F: FnMut(&bounds::T, V) {
+    let contents = self.contents();
+    for x in 0..2 {
+    for y in 0..2 {
+    for z in 0..2 {
+      let bounds = bounds::new(x as i32 - 1, y as i32 - 1, z as i32 - 1, self.lg_size as i16);
+      for_each_in(contents.child(x, y, z), &bounds, region, f);
+    }}}
+  }
+
+  /// Cast a ray through the contents of this tree.
+  pub fn cast_ray<Act, R>(&self, ray: &Ray3<f32>, act: &mut Act) -> Option<R>
+    where Act: FnMut(bounds::T, V) -> Option<R>
+  {
+    let coords = [
+      if ray.origin.x >= 0.0 {1} else {0},
+      if ray.origin.y >= 0.0 {1} else {0},
+      if ray.origin.z >= 0.0 {1} else {0},
+    ];
+    let child_lg_size = self.lg_size as i16;
+    let mut make_bounds = |coords: [usize; 3]| {
+      bounds::new(coords[0] as i32 - 1, coords[1] as i32 - 1, coords[2] as i32 - 1, child_lg_size)
+    };
+    raycast::cast_ray_branches(self.contents(), ray, None, coords, &mut make_bounds, act).ok()
+  }
+}
+
+fn for_each_in<'a, V, F>(node: Node<'a, V>, bounds: &bounds::T, region: &bounds::T, f: &mut F) where
+  V: save::Voxel,
+  F: FnMut(&bounds::T, V),
+{
+  let inside = region.contains(bounds);
+  if !inside && !bounds.contains(region) {
+    return
+  }
+
+  if inside {
+    if let Some(voxel) = node.data() {
+      f(bounds, voxel);
+    }
+  }
+  if let Some(branches) = node.branches() {
+    for x in 0..2 {
+    for y in 0..2 {
+    for z in 0..2 {
+      for_each_in(branches.child(x, y, z), &tree::child_bounds(bounds, x, y, z), region, f);
+    }}}
+  }
+}
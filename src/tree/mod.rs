@@ -4,8 +4,17 @@ use collision::{Aabb, Ray3};
 use cgmath::{Vector3, ElementWise};
 use std;
 
+pub mod codec;
+pub mod dirty;
+pub mod flat;
+#[cfg(feature = "parallel")]
+mod parallel;
 mod raycast;
+pub mod region;
+pub mod save;
+pub mod stats;
 pub mod traversal;
+pub mod validate;
 
 use brush;
 use bounds;
@@ -48,7 +57,104 @@ impl<Voxel> Node<Voxel> {
   pub fn brush<Material, Mosaic, Generate, OnVoxelUpdate>(
     &mut self,
     bounds: &bounds::T,
-    brush: &mut brush::T<Mosaic>,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &mut Generate,
+    on_voxel_update: &mut OnVoxelUpdate,
+  ) where
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material>,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+    OnVoxelUpdate: FnMut(&Voxel, &::bounds::T),
+  {
+    self.brush_data(bounds, brush, mode, generate, on_voxel_update);
+    if self.brush_is_homogeneous(bounds, brush, mode) {
+      return
+    }
+    self.next.brush(bounds, brush, mode, generate, on_voxel_update);
+  }
+
+  // Can a brush leave this node's children alone? If so, children that the brush would
+  // overwrite are dropped.
+  fn brush_is_homogeneous<Material, Mosaic>(
+    &mut self,
+    bounds: &bounds::T,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+  ) -> bool where
+    Mosaic: mosaic::T<Material>,
+  {
+    let has_branches = match self.next { Inner::Empty => false, Inner::Branches(_) => true };
+    match brush_refinement(bounds, brush, mode, self.data.is_some(), has_branches) {
+      Refinement::Descend => false,
+      Refinement::Stop => true,
+      Refinement::Collapse => {
+        debug!("collapsing {:?}", bounds);
+        self.next = Inner::Empty;
+        true
+      },
+    }
+  }
+
+  // Find the changes `brush` would make to this node and its children, without making them.
+  fn brush_preview<Material, Mosaic, Generate>(
+    &self,
+    bounds: &bounds::T,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &mut Generate,
+    changes: &mut Vec<(bounds::T, Voxel)>,
+  ) where
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material> + Clone + PartialEq,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+  {
+    let data =
+      match self.data {
+        Some(ref voxel) => {
+          let mut brushed = voxel.clone();
+          ::T::brush(&mut brushed, bounds, brush, mode);
+          if brushed != *voxel {
+            changes.push((*bounds, brushed.clone()));
+          }
+          Some(brushed)
+        },
+        None => {
+          generate(bounds).map(|mut voxel| {
+            ::T::brush(&mut voxel, bounds, brush, mode);
+            changes.push((*bounds, voxel.clone()));
+            voxel
+          })
+        },
+      };
+
+    let has_branches = match self.next { Inner::Empty => false, Inner::Branches(_) => true };
+    match brush_refinement(bounds, brush, mode, data.is_some(), has_branches) {
+      Refinement::Descend => {},
+      Refinement::Stop | Refinement::Collapse => return,
+    }
+    if !brush_descends(bounds, brush) {
+      return
+    }
+
+    let empty = Branches::empty();
+    let branches =
+      match self.next {
+        Inner::Empty => &empty,
+        Inner::Branches(ref branches) => &**branches,
+      };
+    for (i, child) in branches.as_flat_array().iter().enumerate() {
+      let child_bounds = child_bounds(bounds, i >> 2, (i >> 1) & 1, i & 1);
+      child.brush_preview(&child_bounds, brush, mode, generate, changes);
+    }
+  }
+
+  // Apply a brush to this node's voxel, but not its children.
+  fn brush_data<Material, Mosaic, Generate, OnVoxelUpdate>(
+    &mut self,
+    bounds: &bounds::T,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
     generate: &mut Generate,
     on_voxel_update: &mut OnVoxelUpdate,
   ) where
@@ -62,19 +168,17 @@ impl<Voxel> Node<Voxel> {
         match generate(bounds) {
           None => {},
           Some(mut voxel) => {
-            ::T::brush(&mut voxel, bounds, brush);
+            ::T::brush(&mut voxel, bounds, brush, mode);
             on_voxel_update(&voxel, bounds);
             self.data = Some(voxel);
           },
         }
       },
       Some(ref mut voxel) => {
-        ::T::brush(voxel, bounds, brush);
+        ::T::brush(voxel, bounds, brush, mode);
         on_voxel_update(&voxel, bounds);
       },
     }
-
-    self.next.brush(bounds, brush, generate, on_voxel_update);
   }
 
   /// Return the `Branches` data from this subtree. If none exists, create empty branch data.
@@ -151,6 +255,30 @@ impl<Voxel> Branches<Voxel> {
   }
 }
 
+/// The bounds of a child node, given the bounds of its parent and the child's
+/// position in `Branches::as_array`.
+pub fn child_bounds(parent: &bounds::T, x: usize, y: usize, z: usize) -> bounds::T {
+  bounds::new(
+    (parent.x << 1) + x as i32,
+    (parent.y << 1) + y as i32,
+    (parent.z << 1) + z as i32,
+    parent.lg_size - 1,
+  )
+}
+
+fn for_each_node<Voxel, F>(node: &Node<Voxel>, bounds: &bounds::T, f: &mut F)
+  where F: FnMut(&bounds::T, &Node<Voxel>)
+{
+  f(bounds, node);
+  if let Inner::Branches(ref branches) = node.next {
+    for (x, plane) in branches.as_array().iter().enumerate() {
+    for (y, row) in plane.iter().enumerate() {
+    for (z, child) in row.iter().enumerate() {
+      for_each_node(child, &child_bounds(bounds, x, y, z), f);
+    }}}
+  }
+}
+
 fn brush_overlaps(voxel: &bounds::T, brush: &brush::Bounds) -> bool {
   if voxel.lg_size >= 0 {
     let min =
@@ -195,6 +323,71 @@ fn brush_overlaps(voxel: &bounds::T, brush: &brush::Bounds) -> bool {
   }
 }
 
+fn brush_contains(brush: &brush::Bounds, voxel: &bounds::T) -> bool {
+  let (low, high) = voxel.corners();
+  brush.min().x as f32 <= low.x && high.x <= brush.max().x as f32 &&
+  brush.min().y as f32 <= low.y && high.y <= brush.max().y as f32 &&
+  brush.min().z as f32 <= low.z && high.z <= brush.max().z as f32
+}
+
+// What a brush should do with the children of a node whose voxel it's just brushed.
+enum Refinement {
+  Descend,
+  // The node's voxel holds the result by itself, because the brush's mosaic is the same
+  // everywhere in the node.
+  Stop,
+  // Like `Stop`, but the node's children should be dropped because the brush overwrites them.
+  Collapse,
+}
+
+fn brush_refinement<Material, Mosaic>(
+  bounds: &bounds::T,
+  brush: &brush::T<Mosaic>,
+  mode: &brush::Mode<Material>,
+  has_data: bool,
+  has_branches: bool,
+) -> Refinement where
+  Mosaic: mosaic::T<Material>,
+{
+  if !has_data {
+    return Refinement::Descend
+  }
+
+  let (low, high) = bounds.corners();
+  match mosaic::T::homogeneous(&brush.mosaic, &low, &high) {
+    None => Refinement::Descend,
+    // Nothing changes outside the mosaic.
+    Some(None) => Refinement::Stop,
+    Some(Some(_)) => {
+      // A soft brush's weight varies across the node, so its children may need to differ.
+      if !has_branches && brush.is_hard() {
+        return Refinement::Stop
+      }
+      let overwrites =
+        match *mode {
+          brush::Mode::Add | brush::Mode::Subtract { .. } => true,
+          brush::Mode::Replace { .. } | brush::Mode::Paint { .. } => false,
+        };
+      if overwrites && brush.is_hard() && brush_contains(&brush.bounds, bounds) {
+        Refinement::Collapse
+      } else {
+        Refinement::Descend
+      }
+    },
+  }
+}
+
+// Should a brush be applied to the children of the node at `bounds`?
+fn brush_descends<Mosaic>(bounds: &bounds::T, brush: &brush::T<Mosaic>) -> bool {
+  debug!("brush considers {:?}", bounds);
+  if !brush_overlaps(bounds, &brush.bounds) {
+    debug!("ignoring {:?}", bounds);
+    return false
+  }
+
+  bounds.lg_size >= brush.min_lg_size
+}
+
 impl<Voxel> Inner<Voxel> {
   /// Return the `Branches` data from this subtree. If none exists, create empty branch data.
   pub fn force_branches(&mut self) -> &mut Branches<Voxel> {
@@ -216,7 +409,8 @@ impl<Voxel> Inner<Voxel> {
   pub fn brush<Material, Mosaic, Generate, OnVoxelUpdate>(
     &mut self,
     bounds: &bounds::T,
-    brush: &mut brush::T<Mosaic>,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
     generate: &mut Generate,
     on_voxel_update: &mut OnVoxelUpdate,
   ) where
@@ -225,13 +419,7 @@ impl<Voxel> Inner<Voxel> {
     Generate: FnMut(&::bounds::T) -> Option<Voxel>,
     OnVoxelUpdate: FnMut(&Voxel, &::bounds::T),
   {
-    debug!("brush considers {:?}", bounds);
-    if !brush_overlaps(bounds, &brush.bounds) {
-      debug!("ignoring {:?}", bounds);
-      return
-    }
-
-    if bounds.lg_size < brush.min_lg_size {
+    if !brush_descends(bounds, brush) {
       return
     }
 
@@ -242,7 +430,7 @@ impl<Voxel> Inner<Voxel> {
       macro_rules! recurse(($branch: ident, $update_bounds: expr) => {{
         let mut bounds = bounds;
         $update_bounds(&mut bounds);
-        branches.$branch.brush(&bounds, brush, generate, on_voxel_update);
+        branches.$branch.brush(&bounds, brush, mode, generate, on_voxel_update);
       }});
       recurse!(lll, |_|                 {                            });
       recurse!(llh, |b: &mut bounds::T| {                    b.z += 1});
@@ -298,6 +486,48 @@ impl<Voxel> T<Voxel> {
     }
   }
 
+  /// The bounds of one of the top-level nodes, given its position in `Branches::as_array`.
+  pub fn top_bounds(&self, x: usize, y: usize, z: usize) -> bounds::T {
+    bounds::new(x as i32 - 1, y as i32 - 1, z as i32 - 1, self.lg_size as i16)
+  }
+
+  /// Call `f` on every node in this tree, parents before their children.
+  pub fn for_each_node<F>(&self, f: &mut F)
+    where F: FnMut(&bounds::T, &Node<Voxel>)
+  {
+    for (x, plane) in self.contents.as_array().iter().enumerate() {
+    for (y, row) in plane.iter().enumerate() {
+    for (z, node) in row.iter().enumerate() {
+      for_each_node(node, &self.top_bounds(x, y, z), f);
+    }}}
+  }
+
+  /// Collect memory and shape statistics about this tree.
+  pub fn stats(&self) -> stats::T {
+    let mut stats = stats::empty();
+    let lg_size = self.lg_size as i16;
+    self.for_each_node(&mut |bounds, node| {
+      stats.add((lg_size - bounds.lg_size) as u32 + 1, bounds, node)
+    });
+    stats
+  }
+
+  /// Check that every stored node lies within this tree's bounds.
+  /// Every violation found is returned, rather than stopping at the first one.
+  pub fn validate(&self) -> Vec<validate::Violation> {
+    self.validate_with(&mut |_, _, _, _| true)
+  }
+
+  /// Like `validate`, but also check that `consistent(parent_bounds, parent, child_bounds, child)`
+  /// holds for every parent voxel and each of its direct children.
+  pub fn validate_with<Consistent>(&self, consistent: &mut Consistent) -> Vec<validate::Violation>
+    where Consistent: FnMut(&bounds::T, &Voxel, &bounds::T, &Voxel) -> bool
+  {
+    let mut violations = Vec::new();
+    validate::check(self, consistent, &mut |violation| violations.push(violation));
+    violations
+  }
+
   /// Ensure that this tree can hold the provided voxel.
   pub fn grow_to_hold(&mut self, voxel: &bounds::T) {
     while !self.contains_bounds(voxel) {
@@ -459,7 +689,8 @@ impl<Voxel> T<Voxel> {
   /// Apply a voxel brush to the contents of this tree.
   pub fn brush<Material, Mosaic, Generate, OnVoxelUpdate>(
     &mut self,
-    brush: &mut brush::T<Mosaic>,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
     generate: &mut Generate,
     on_voxel_update: &mut OnVoxelUpdate,
   ) where
@@ -472,6 +703,7 @@ impl<Voxel> T<Voxel> {
       self.contents.$branch.brush(
         &bounds::new($x, $y, $z, self.lg_size as i16),
         brush,
+        mode,
         generate,
         on_voxel_update
       );
@@ -485,6 +717,65 @@ impl<Voxel> T<Voxel> {
     recurse!(hhl,  0,  0, -1);
     recurse!(hhh,  0,  0,  0);
   }
+
+  /// Apply a voxel brush to the contents of this tree, and return the chunks of size
+  /// `2^chunk_lg_size` that need to be remeshed. To accumulate chunks over several brushes,
+  /// call `dirty::T::mark` from `brush`'s `on_voxel_update` instead.
+  pub fn brush_dirty<Material, Mosaic, Generate>(
+    &mut self,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &mut Generate,
+    chunk_lg_size: i16,
+  ) -> dirty::T where
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material>,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+  {
+    let mut dirty = dirty::new(chunk_lg_size);
+    self.brush(brush, mode, generate, &mut |_, bounds| dirty.mark(bounds));
+    dirty
+  }
+
+  /// Find the changes `brush` would make to the contents of this tree, without making them.
+  /// Each voxel that would change is returned along with what it would become, in the order
+  /// `brush` would change them. Children that `brush` would drop entirely aren't listed.
+  pub fn brush_preview<Material, Mosaic, Generate>(
+    &self,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &mut Generate,
+  ) -> Vec<(bounds::T, Voxel)> where
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material> + Clone + PartialEq,
+    Generate: FnMut(&::bounds::T) -> Option<Voxel>,
+  {
+    let mut changes = Vec::new();
+    for (i, node) in self.contents.as_flat_array().iter().enumerate() {
+      let bounds = self.top_bounds(i >> 2, (i >> 1) & 1, i & 1);
+      node.brush_preview(&bounds, brush, mode, generate, &mut changes);
+    }
+    changes
+  }
+
+  /// Apply a voxel brush to the contents of this tree, splitting disjoint subtrees across threads.
+  /// The result is the same as `brush`, but `on_voxel_update` may be called in any order.
+  #[cfg(feature = "parallel")]
+  pub fn brush_parallel<Material, Mosaic, Generate, OnVoxelUpdate>(
+    &mut self,
+    brush: &brush::T<Mosaic>,
+    mode: &brush::Mode<Material>,
+    generate: &Generate,
+    on_voxel_update: &OnVoxelUpdate,
+  ) where
+    Material: Sync,
+    Mosaic: mosaic::T<Material>,
+    Voxel: ::T<Material> + Send,
+    Generate: Fn(&::bounds::T) -> Option<Voxel> + Sync,
+    OnVoxelUpdate: Fn(&Voxel, &::bounds::T) + Sync,
+  {
+    parallel::brush(self, brush, mode, generate, on_voxel_update)
+  }
 }
 
 #[cfg(test)]
@@ -499,23 +790,24 @@ mod tests {
   use bounds;
   use brush;
   use field;
+  use impls::surface_vertex;
   use mosaic;
 
-  #[derive(Debug)]
+  #[derive(Debug, Clone)]
   struct EraseAll;
 
   impl field::T for EraseAll {
-    fn density(&mut self, _: &Point3<f32>) -> f32 {
+    fn density(&self, _: &Point3<f32>) -> f32 {
       1.0
     }
 
-    fn normal(&mut self, _: &Point3<f32>) -> Vector3<f32> {
+    fn normal(&self, _: &Point3<f32>) -> Vector3<f32> {
       Vector3::new(0.0, 0.0, 0.0)
     }
   }
 
   impl mosaic::T<()> for EraseAll {
-    fn material(&mut self, _: &Point3<f32>) -> Option<()> {
+    fn material(&self, _: &Point3<f32>) -> Option<()> {
       None
     }
   }
@@ -524,7 +816,8 @@ mod tests {
     fn brush<Mosaic>(
       this: &mut Self,
       _: &bounds::T,
-      _: &mut brush::T<Mosaic>,
+      _: &brush::T<Mosaic>,
+      _: &brush::Mode<()>,
     ) where Mosaic: mosaic::T<()>
     {
       *this = 999;
@@ -584,6 +877,224 @@ mod tests {
     assert_eq!(tree.get(&bounds::new(4, 4, -4, 2)), None);
   }
 
+  #[test]
+  fn stats_count_nodes() {
+    let mut tree: T<i32> = super::new();
+    *tree.get_mut_or_create(&bounds::new(1, 1, 1, 0)) = Node::leaf(Some(1));
+    tree.get_mut_or_create(&bounds::new(-2, -2, -2, 0)).force_branches();
+
+    let stats = tree.stats();
+    assert_eq!(tree.lg_size, 1);
+    assert_eq!(stats.nodes_by_lg_size.get(&1), Some(&8));
+    // Growing the tree gives every top-level node branches.
+    assert_eq!(stats.nodes_by_lg_size.get(&0), Some(&64));
+    assert_eq!(stats.nodes_by_lg_size.get(&-1), Some(&8));
+    assert_eq!(stats.nodes(), 80);
+    assert_eq!(stats.populated, 1);
+    assert_eq!(stats.empty_branches, 7);
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!(stats.heap_bytes, 9 * std::mem::size_of::<Branches<i32>>());
+  }
+
+  #[test]
+  fn validate_reports_every_violation() {
+    let mut tree: T<i32> = super::new();
+    *tree.get_mut_or_create(&bounds::new(0, 0, 0, 1)) = Node::leaf(Some(1));
+    *tree.get_mut_or_create(&bounds::new(1, 1, 1, 0)) = Node::leaf(Some(2));
+    *tree.get_mut_or_create(&bounds::new(0, 1, 1, 0)) = Node::leaf(Some(1));
+    *tree.get_mut_or_create(&bounds::new(1, 0, 1, 0)) = Node::leaf(Some(3));
+    assert_eq!(tree.validate(), vec!());
+
+    let violations = tree.validate_with(&mut |_, parent, _, child| parent == child);
+    let parent = bounds::new(0, 0, 0, 1);
+    assert_eq!(
+      violations,
+      vec!(
+        validate::Violation {
+          bounds: bounds::new(1, 0, 1, 0),
+          problem: validate::Problem::Inconsistent(parent),
+        },
+        validate::Violation {
+          bounds: bounds::new(1, 1, 1, 0),
+          problem: validate::Problem::Inconsistent(parent),
+        },
+      ),
+    );
+
+    let mut deep: T<i32> = super::new();
+    {
+      let mut node = &mut deep.contents.as_flat_array_mut()[7];
+      for _ in 0 .. validate::MAX_LG_SIZE + 1 {
+        node = &mut node.force_branches().as_flat_array_mut()[0];
+      }
+      node.data = Some(1);
+    }
+    assert_eq!(
+      deep.validate(),
+      vec!(validate::Violation {
+        bounds: bounds::new(0, 0, 0, -(validate::MAX_LG_SIZE as i16) - 1),
+        problem: validate::Problem::OutOfBounds,
+      }),
+    );
+
+    tree.lg_size = 40;
+    assert_eq!(
+      tree.validate(),
+      vec!(validate::Violation {
+        bounds: bounds::new(0, 0, 0, 40),
+        problem: validate::Problem::TooLarge,
+      }),
+    );
+  }
+
+  #[test]
+  fn save_round_trip() {
+    let mut tree: T<i32> = super::new();
+    *tree.get_mut_or_create(&bounds::new(1, 1, 1, 0)) = Node::leaf(Some(1));
+    *tree.get_mut_or_create(&bounds::new(8, -8, 4, 0)) = Node::leaf(Some(2));
+    *tree.get_mut_or_create(&bounds::new(2, 0, 4, 4)) = Node::leaf(Some(3));
+
+    let mut bytes = Vec::new();
+    save::write(&mut bytes, &tree).unwrap();
+    let loaded: T<i32> = save::read(&mut &bytes[..]).unwrap();
+    assert_eq!(loaded.lg_size, tree.lg_size);
+    assert_eq!(loaded.contents, tree.contents);
+
+    // Saves of other voxel types go through the migration hook.
+    let mut migrated = false;
+    let loaded: T<i64> =
+      save::read_with(&mut &bytes[..], &mut |header, r| {
+        assert_eq!(*header, save::Header::current::<i32>());
+        migrated = true;
+        let tree: T<i32> = save::read_body(r)?;
+        Ok(T {
+          lg_size: tree.lg_size,
+          contents: Branches::empty(),
+        })
+      }).unwrap();
+    assert!(migrated);
+    assert_eq!(loaded.lg_size, tree.lg_size);
+
+    match save::read::<_, i32>(&mut &b"not a save"[..]) {
+      Err(save::Error::BadMagic(magic)) => assert_eq!(&magic, b"not "),
+      r => panic!("unexpected {:?}", r),
+    }
+  }
+
+  #[test]
+  fn save_reads_version_1() {
+    let mut bytes = Vec::new();
+    save::write_header(&mut bytes, &save::Header { version: 1, voxel_type: "i32".to_string() }).unwrap();
+    // lg_size
+    bytes.push(0);
+    // One top-level node with a voxel, the rest empty.
+    bytes.extend_from_slice(&[1, 7, 0, 0, 0]);
+    bytes.extend_from_slice(&[0; 7]);
+
+    let tree: T<i32> = save::read(&mut &bytes[..]).unwrap();
+    assert_eq!(tree.get(&bounds::new(-1, -1, -1, 0)), Some(&7));
+    assert_eq!(tree.get(&bounds::new(0, 0, 0, 0)), None);
+  }
+
+  #[test]
+  fn compact_encoding() {
+    let mut tree: T<i32> = super::new();
+    tree.grow_to_hold(&bounds::new(0, 0, 0, 4));
+    for x in 0..8 {
+      *tree.get_mut_or_create(&bounds::new(x, 1, 2, 0)) = Node::leaf(Some(3));
+    }
+    *tree.get_mut_or_create(&bounds::new(-3, -1, 2, 1)) = Node::leaf(Some(4));
+
+    let mut plain = Vec::new();
+    codec::write(&mut plain, tree.contents.as_flat_array(), codec::Compression::None).unwrap();
+    let mut rle = Vec::new();
+    codec::write(&mut rle, tree.contents.as_flat_array(), codec::Compression::RunLength).unwrap();
+    assert!(rle.len() < plain.len());
+
+    for bytes in &[plain, rle] {
+      let mut contents = Branches::empty();
+      codec::read(&mut &bytes[..], contents.as_flat_array_mut(), 32).unwrap();
+      assert_eq!(contents, tree.contents);
+    }
+
+    // Truncated input is an error, not a panic.
+    let mut bytes = Vec::new();
+    save::write_with(&mut bytes, &tree, codec::Compression::RunLength).unwrap();
+    let len = bytes.len();
+    assert!(save::read::<_, i32>(&mut &bytes[.. len - 1]).is_err());
+    assert_eq!(save::read::<_, i32>(&mut &bytes[..]).unwrap().contents, tree.contents);
+  }
+
+  #[test]
+  fn region_round_trip() {
+    let mut tree: T<i32> = super::new();
+    *tree.get_mut_or_create(&bounds::new(1, 1, 1, 0)) = Node::leaf(Some(1));
+    *tree.get_mut_or_create(&bounds::new(2, 3, 0, 0)) = Node::leaf(Some(2));
+    *tree.get_mut_or_create(&bounds::new(0, 0, 0, 2)) = Node::leaf(Some(3));
+    *tree.get_mut_or_create(&bounds::new(-1, 0, 0, 0)) = Node::leaf(Some(4));
+
+    let region = bounds::new(0, 0, 0, 2);
+    let mut bytes = Vec::new();
+    region::write(&mut bytes, &tree, &region, codec::Compression::RunLength).unwrap();
+
+    let mut loaded: T<i32> = super::new();
+    *loaded.get_mut_or_create(&bounds::new(3, 3, 3, 0)) = Node::leaf(Some(5));
+    assert_eq!(region::read(&mut &bytes[..], &mut loaded).unwrap(), region);
+    assert_eq!(loaded.get_pointer(&region), tree.get_pointer(&region));
+    assert_eq!(loaded.get(&bounds::new(3, 3, 3, 0)), None);
+    assert_eq!(loaded.get(&bounds::new(-1, 0, 0, 0)), None);
+  }
+
+  #[test]
+  fn flat_view() {
+    let mut tree: T<i32> = super::new();
+    *tree.get_mut_or_create(&bounds::new(1, 1, 1, 0)) = Node::leaf(Some(1));
+    *tree.get_mut_or_create(&bounds::new(4, 4, 4, 0)) = Node::leaf(Some(2));
+    tree.get_mut_or_create(&bounds::new(2, 2, 2, 1)).data = Some(3);
+    *tree.get_mut_or_create(&bounds::new(-3, 0, 2, 0)) = Node::leaf(Some(4));
+
+    let mut bytes = Vec::new();
+    flat::write(&mut bytes, &tree).unwrap();
+    let view: flat::View<i32> = flat::view(&bytes).unwrap();
+
+    assert_eq!(view.get(&bounds::new(1, 1, 1, 0)), Some(1));
+    assert_eq!(view.get(&bounds::new(4, 4, 4, 0)), Some(2));
+    assert_eq!(view.get(&bounds::new(2, 2, 2, 1)), Some(3));
+    assert_eq!(view.get(&bounds::new(-3, 0, 2, 0)), Some(4));
+    assert_eq!(view.get(&bounds::new(1, 1, 0, 0)), None);
+    assert_eq!(view.get(&bounds::new(100, 1, 0, 0)), None);
+
+    let mut found = Vec::new();
+    view.for_each_in(&bounds::new(0, 0, 0, 3), &mut |bounds, voxel| found.push((*bounds, voxel)));
+    found.sort_by_key(|&(_, voxel)| voxel);
+    assert_eq!(
+      found,
+      vec!(
+        (bounds::new(1, 1, 1, 0), 1),
+        (bounds::new(4, 4, 4, 0), 2),
+        (bounds::new(2, 2, 2, 1), 3),
+      ),
+    );
+
+    let ray = Ray3::new(Point3::new(4.5, 3.0, 4.5), Vector3::new(0.1, 0.8, 0.1));
+    assert_eq!(
+      view.cast_ray(&ray, &mut |bounds, v| Some((bounds, v))),
+      tree.cast_ray(&ray, &mut |bounds, v| Some((bounds, *v))),
+    );
+
+    // Garbage doesn't cause panics.
+    let len = bytes.len();
+    for i in 16 .. len {
+      let mut bytes = bytes.clone();
+      bytes[i] = bytes[i].wrapping_add(0x55);
+      if let Ok(view) = flat::view::<i32>(&bytes) {
+        view.get(&bounds::new(1, 1, 1, 0));
+        view.for_each_in(&bounds::new(-1, -1, -1, 3), &mut |_, _| {});
+        view.cast_ray(&ray, &mut |_, _| None::<()>);
+      }
+    }
+  }
+
   #[test]
   fn grow_is_transparent() {
     let mut tree: T<i32> = super::new();
@@ -616,7 +1127,7 @@ mod tests {
     *tree.get_mut_or_create(&bounds::new(9, -1, 3, 0)) = Node::leaf(Some(1));
 
     tree.brush(
-      &mut brush::T {
+      &brush::T {
         mosaic: EraseAll,
         bounds:
           brush::Bounds::new(
@@ -624,7 +1135,10 @@ mod tests {
             Point3::new(10, 0, 4),
           ),
         min_lg_size: 0,
+        strength: 1.0,
+        falloff: None,
       },
+      &brush::Mode::Add,
       &mut |_| None,
       &mut |_, _| {},
     );
@@ -632,6 +1146,127 @@ mod tests {
     assert_eq!(tree.get(&bounds::new(9, -1, 3, 0)), Some(&999));
   }
 
+  #[test]
+  fn dirty_chunks_include_low_neighbors() {
+    let mut dirty = dirty::new(2);
+    dirty.mark(&bounds::new(5, 5, 5, 0));
+    assert_eq!(dirty.chunks.len(), 1);
+    dirty.mark(&bounds::new(4, 5, 5, 0));
+    assert!(dirty.chunks.contains(&bounds::new(0, 1, 1, 2)));
+    assert_eq!(dirty.chunks.len(), 2);
+    dirty.mark(&bounds::new(4, 4, 4, 0));
+    assert_eq!(dirty.chunks.len(), 8);
+
+    let mut dirty = dirty::new(2);
+    dirty.mark(&bounds::new(1, 0, 0, 3));
+    assert_eq!(dirty.chunks.len(), 27);
+
+    let mut tree: T<i32> = super::new();
+    *tree.get_mut_or_create(&bounds::new(9, -1, 3, 0)) = Node::leaf(Some(1));
+    let brush = brush::new(brush::Bounds::new(Point3::new(9, -1, 3), Point3::new(10, 0, 4)), EraseAll, 0);
+    let dirty = tree.brush_dirty(&brush, &brush::Mode::Add, &mut |_| None, 3);
+    assert!(dirty.chunks.contains(&bounds::new(1, -1, 0, 3)));
+    assert_eq!(tree.get(&bounds::new(9, -1, 3, 0)), Some(&999));
+  }
+
+  #[test]
+  fn homogeneous_brushes_stop_early() {
+    let mut tree: T<surface_vertex::T<u8>> = super::new();
+    tree.grow_to_hold(&bounds::new(0, 0, 0, 6));
+    let brush =
+      brush::new(
+        brush::Bounds::new(Point3::new(-64, -64, -64), Point3::new(64, 64, 64)),
+        mosaic::solid::T { field: field::sphere::T { radius: 60.0 }, material: 1 },
+        0,
+      );
+    tree.brush(&brush, &brush::Mode::Add, &mut |_| Some(surface_vertex::T::Volume(0)), &mut |_, _| {});
+
+    // The middle of the sphere is a few large voxels.
+    assert_eq!(tree.get(&bounds::new(0, 0, 0, 5)), Some(&surface_vertex::T::Volume(1)));
+    assert!(tree.get_pointer(&bounds::new(0, 0, 0, 4)).is_none());
+    assert!(tree.get_pointer(&bounds::new(59, 0, 0, 0)).is_some());
+    // Refining the whole brush would take more than 128^3 nodes.
+    assert!(tree.stats().nodes() < 1 << 20);
+  }
+
+  #[test]
+  fn soft_brushes_refine_homogeneous_leaves() {
+    let mut tree: T<surface_vertex::T<u8>> = super::new();
+    tree.grow_to_hold(&bounds::new(0, 0, 0, 3));
+    tree.get_mut_or_create(&bounds::new(0, 0, 0, 3)).data = Some(surface_vertex::T::Volume(0));
+    let mut brush =
+      brush::new(
+        brush::Bounds::new(Point3::new(0, 0, 0), Point3::new(8, 8, 8)),
+        mosaic::solid::T { field: field::sphere::T { radius: 60.0 }, material: 1 },
+        0,
+      );
+    brush.falloff = Some(brush::Falloff::within(&brush.bounds, brush::Profile::Linear));
+    tree.brush(&brush, &brush::Mode::Add, &mut |_| Some(surface_vertex::T::Volume(0)), &mut |_, _| {});
+
+    // The brush is strong in the middle of the leaf, and weak at its corners.
+    assert_eq!(tree.get(&bounds::new(3, 3, 3, 0)), Some(&surface_vertex::T::Volume(1)));
+    assert_eq!(tree.get(&bounds::new(0, 0, 0, 0)), Some(&surface_vertex::T::Volume(0)));
+  }
+
+  #[test]
+  fn brush_preview_matches_brush() {
+    let mut tree: T<surface_vertex::T<u8>> = super::new();
+    tree.grow_to_hold(&bounds::new(0, 0, 0, 3));
+    tree.get_mut_or_create(&bounds::new(1, 1, 1, 0)).data = Some(surface_vertex::T::Volume(2));
+    let brush =
+      brush::new(
+        brush::Bounds::new(Point3::new(-4, -4, -4), Point3::new(4, 4, 4)),
+        mosaic::solid::T { field: field::sphere::T { radius: 3.0 }, material: 1 },
+        0,
+      );
+    let mode = brush::Mode::Replace { filter: 0 };
+    let mut generate = |b: &bounds::T| if b.lg_size == 0 { Some(surface_vertex::T::Volume(0)) } else { None };
+
+    let before = tree.contents.clone();
+    let preview = tree.brush_preview(&brush, &mode, &mut generate);
+    assert_eq!(tree.contents, before);
+    assert!(!preview.iter().any(|&(b, _)| b == bounds::new(1, 1, 1, 0)));
+
+    let mut updates = Vec::new();
+    tree.brush(&brush, &mode, &mut generate, &mut |v, b| updates.push((*b, *v)));
+    for change in &preview {
+      assert_eq!(tree.get(&change.0), Some(&change.1));
+    }
+    // `brush` reports every voxel it visits, but the preview leaves out voxels the brush doesn't
+    // change, like the filtered-out one at (1, 1, 1).
+    assert!(updates.contains(&(bounds::new(1, 1, 1, 0), surface_vertex::T::Volume(2))));
+    updates.retain(|&(b, _)| b != bounds::new(1, 1, 1, 0));
+    assert_eq!(preview.len(), updates.len());
+    assert!(preview.iter().all(|change| updates.contains(change)));
+    assert!(updates.iter().all(|update| preview.contains(update)));
+  }
+
+  #[cfg(feature = "parallel")]
+  #[test]
+  fn parallel_brush_matches_serial() {
+    let mut serial: T<i32> = super::new();
+    serial.grow_to_hold(&bounds::new(0, 0, 0, 4));
+    *serial.get_mut_or_create(&bounds::new(9, -1, 3, 0)) = Node::leaf(Some(1));
+    let mut parallel = T {
+      lg_size: serial.lg_size,
+      contents: serial.contents.clone(),
+    };
+
+    let brush = brush::new(brush::Bounds::new(Point3::new(-3, -5, 0), Point3::new(10, 0, 4)), EraseAll, 0);
+    let generate = |bounds: &bounds::T| if bounds.lg_size == 0 { Some(0) } else { None };
+
+    let mut serial_updates = Vec::new();
+    serial.brush(&brush, &brush::Mode::Add, &mut |b| generate(b), &mut |_, b| serial_updates.push(*b));
+    let parallel_updates = std::sync::Mutex::new(Vec::new());
+    parallel.brush_parallel(&brush, &brush::Mode::Add, &generate, &|_, b| parallel_updates.lock().unwrap().push(*b));
+
+    assert_eq!(parallel.contents, serial.contents);
+    let mut parallel_updates = parallel_updates.into_inner().unwrap();
+    parallel_updates.sort_by_key(|b| (b.x, b.y, b.z, b.lg_size));
+    serial_updates.sort_by_key(|b| (b.x, b.y, b.z, b.lg_size));
+    assert_eq!(parallel_updates, serial_updates);
+  }
+
   #[bench]
   fn simple_inserts(bencher: &mut test::Bencher) {
     bencher.iter(|| {
@@ -0,0 +1,86 @@
+//! Brush application that splits disjoint subtrees across threads.
+
+use rayon::prelude::*;
+
+use bounds;
+use brush;
+use mosaic;
+use tree;
+
+/// How many levels below the top of the tree get their own tasks.
+/// Below this, subtrees are brushed serially.
+const TASK_DEPTH: u32 = 3;
+
+fn brush_node<Voxel, Material, Mosaic, Generate, OnVoxelUpdate>(
+  node: &mut tree::Node<Voxel>,
+  bounds: &bounds::T,
+  brush: &brush::T<Mosaic>,
+  mode: &brush::Mode<Material>,
+  generate: &Generate,
+  on_voxel_update: &OnVoxelUpdate,
+  depth_left: u32,
+) where
+  Material: Sync,
+  Mosaic: mosaic::T<Material>,
+  Voxel: ::T<Material> + Send,
+  Generate: Fn(&bounds::T) -> Option<Voxel> + Sync,
+  OnVoxelUpdate: Fn(&Voxel, &bounds::T) + Sync,
+{
+  let mut local_generate = |bounds: &bounds::T| generate(bounds);
+  let mut local_on_voxel_update = |voxel: &Voxel, bounds: &bounds::T| on_voxel_update(voxel, bounds);
+
+  if depth_left == 0 {
+    node.brush(bounds, brush, mode, &mut local_generate, &mut local_on_voxel_update);
+    return
+  }
+
+  node.brush_data(bounds, brush, mode, &mut local_generate, &mut local_on_voxel_update);
+  if node.brush_is_homogeneous(bounds, brush, mode) || !tree::brush_descends(bounds, brush) {
+    return
+  }
+
+  brush_branches(node.force_branches(), bounds, brush, mode, generate, on_voxel_update, depth_left - 1);
+}
+
+fn brush_branches<Voxel, Material, Mosaic, Generate, OnVoxelUpdate>(
+  branches: &mut tree::Branches<Voxel>,
+  parent: &bounds::T,
+  brush: &brush::T<Mosaic>,
+  mode: &brush::Mode<Material>,
+  generate: &Generate,
+  on_voxel_update: &OnVoxelUpdate,
+  depth_left: u32,
+) where
+  Material: Sync,
+  Mosaic: mosaic::T<Material>,
+  Voxel: ::T<Material> + Send,
+  Generate: Fn(&bounds::T) -> Option<Voxel> + Sync,
+  OnVoxelUpdate: Fn(&Voxel, &bounds::T) + Sync,
+{
+  branches.as_flat_array_mut().par_iter_mut().enumerate().for_each(|(i, child)| {
+    let bounds = tree::child_bounds(parent, i >> 2, (i >> 1) & 1, i & 1);
+    brush_node(child, &bounds, brush, mode, generate, on_voxel_update, depth_left);
+  });
+}
+
+/// Apply a brush to the whole tree, as `tree::T::brush` does.
+pub fn brush<Voxel, Material, Mosaic, Generate, OnVoxelUpdate>(
+  tree: &mut tree::T<Voxel>,
+  brush: &brush::T<Mosaic>,
+  mode: &brush::Mode<Material>,
+  generate: &Generate,
+  on_voxel_update: &OnVoxelUpdate,
+) where
+  Material: Sync,
+  Mosaic: mosaic::T<Material>,
+  Voxel: ::T<Material> + Send,
+  Generate: Fn(&bounds::T) -> Option<Voxel> + Sync,
+  OnVoxelUpdate: Fn(&Voxel, &bounds::T) + Sync,
+{
+  // The top-level nodes are always brushed, like in `tree::T::brush`.
+  let lg_size = tree.lg_size as i16;
+  tree.contents.as_flat_array_mut().par_iter_mut().enumerate().for_each(|(i, node)| {
+    let bounds = bounds::new((i >> 2) as i32 - 1, ((i >> 1) & 1) as i32 - 1, (i & 1) as i32 - 1, lg_size);
+    brush_node(node, &bounds, brush, mode, generate, on_voxel_update, TASK_DEPTH);
+  });
+}
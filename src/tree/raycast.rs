@@ -49,11 +49,58 @@ pub struct Exit {
   toi: TOI,
 }
 
+/// A handle to a group of eight sibling nodes that rays can be cast through.
+pub trait Branches: Copy {
+  #[allow(missing_docs)]
+  type Node: Node<Branches=Self>;
+
+  /// The child at a given position, as in `tree::Branches::as_array`.
+  fn child(self, coords: [usize; 3]) -> Self::Node;
+}
+
+/// A handle to a node that rays can be cast through.
+pub trait Node: Copy {
+  #[allow(missing_docs)]
+  type Branches: Branches<Node=Self>;
+  /// What gets passed to the callback for a voxel the ray hits.
+  type Voxel;
+
+  #[allow(missing_docs)]
+  fn data(self) -> Option<Self::Voxel>;
+
+  #[allow(missing_docs)]
+  fn branches(self) -> Option<Self::Branches>;
+}
+
+impl<'a, Voxel> Branches for &'a tree::Branches<Voxel> {
+  type Node = &'a tree::Node<Voxel>;
+
+  fn child(self, coords: [usize; 3]) -> Self::Node {
+    &self.as_array()[coords[0]][coords[1]][coords[2]]
+  }
+}
+
+impl<'a, Voxel> Node for &'a tree::Node<Voxel> {
+  type Branches = &'a tree::Branches<Voxel>;
+  type Voxel = &'a Voxel;
+
+  fn data(self) -> Option<&'a Voxel> {
+    self.data.as_ref()
+  }
+
+  fn branches(self) -> Option<&'a tree::Branches<Voxel>> {
+    match self.next {
+      tree::Inner::Empty => None,
+      tree::Inner::Branches(ref branches) => Some(branches),
+    }
+  }
+}
+
 // TODO: Audit all the divisions for divide-by-zeros.
 
 #[inline]
-pub fn cast_ray_branches<'a, Voxel, MakeBounds, Act, R>(
-  this: &'a tree::Branches<Voxel>,
+pub fn cast_ray_branches<B, MakeBounds, Act, R>(
+  this: B,
   ray: &Ray3<f32>,
   mut entry: Option<Entry>,
   mut coords: [usize; 3],
@@ -61,11 +108,12 @@ pub fn cast_ray_branches<'a, Voxel, MakeBounds, Act, R>(
   act: &mut Act,
 ) -> Result<R, Exit>
   where
+    B: Branches,
     MakeBounds: FnMut([usize; 3]) -> bounds::T,
-    Act: FnMut(bounds::T, &'a Voxel) -> Option<R>,
+    Act: FnMut(bounds::T, <B::Node as Node>::Voxel) -> Option<R>,
 {
   loop {
-    let child = &this.as_array()[coords[0]][coords[1]][coords[2]];
+    let child = this.child(coords);
     let bounds = make_bounds(coords);
 
     match cast_ray(child, ray, bounds, entry, act) {
@@ -90,21 +138,22 @@ pub fn cast_ray_branches<'a, Voxel, MakeBounds, Act, R>(
 }
 
 /// Precondition: the ray passes through `this`.
-pub fn cast_ray<'a, Voxel, Act, R>(
-  this: &'a tree::Node<Voxel>,
+pub fn cast_ray<N, Act, R>(
+  this: N,
   ray: &Ray3<f32>,
   bounds: bounds::T,
   entry: Option<Entry>,
   act: &mut Act,
 ) -> Result<R, Exit>
   where
-    Act: FnMut(bounds::T, &'a Voxel) -> Option<R>
+    N: Node,
+    Act: FnMut(bounds::T, N::Voxel) -> Option<R>
 {
-  if let Some(ref voxel) = this.data {
+  if let Some(voxel) = this.data() {
     if let Some(r) = act(bounds, voxel) {
       return Ok(r)
     }
-  } else if let tree::Inner::Branches(ref branches) = this.next {
+  } else if let Some(branches) = this.branches() {
     let mid = bounds.center();
 
     let mut make_bounds = |coords: [usize; 3]| {
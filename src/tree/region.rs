@@ -0,0 +1,84 @@
+//! Streaming (de)serialization of the subtree under a single voxel.
+//!
+//! A region blob is a header like a save's (starting with `MAGIC` instead), the bounds of the
+//! region, and the region's node encoded by `tree::codec`. Nodes are written straight out of the
+//! tree, and read straight into it.
+
+use std::io;
+use std::slice;
+
+use bounds;
+use tree;
+use tree::codec;
+use tree::save;
+use tree::validate;
+
+/// The bytes every region blob starts with.
+pub const MAGIC: [u8; 4] = *b"SVOr";
+
+fn write_bounds<W: io::Write>(w: &mut W, bounds: &bounds::T) -> io::Result<()> {
+  save::write_u32(w, bounds.x as u32)?;
+  save::write_u32(w, bounds.y as u32)?;
+  save::write_u32(w, bounds.z as u32)?;
+  w.write_all(&(bounds.lg_size as u16).to_le_bytes())
+}
+
+fn read_bounds<R: io::Read>(r: &mut R) -> io::Result<bounds::T> {
+  let x = save::read_u32(r)? as i32;
+  let y = save::read_u32(r)? as i32;
+  let z = save::read_u32(r)? as i32;
+  let mut lg_size = [0; 2];
+  r.read_exact(&mut lg_size)?;
+  Ok(bounds::new(x, y, z, u16::from_le_bytes(lg_size) as i16))
+}
+
+/// Write the subtree under `bounds`, which doesn't need to exist in `tree`.
+pub fn write<W, V>(
+  w: &mut W,
+  tree: &tree::T<V>,
+  bounds: &bounds::T,
+  compression: codec::Compression,
+) -> io::Result<()> where
+  W: io::Write,
+  V: save::Voxel + PartialEq,
+{
+  save::write_header_with_magic(w, &MAGIC, &save::Header::current::<V>())?;
+  write_bounds(w, bounds)?;
+  let empty = tree::Node::empty();
+  let node = tree.get_pointer(bounds).unwrap_or(&empty);
+  codec::write(w, slice::from_ref(node), compression)
+}
+
+/// Read a region written by `write` into `tree`, growing it if necessary.
+/// The region replaces whatever was in `tree` under its bounds, which are returned.
+/// If reading fails partway through, the region may be left partially loaded.
+pub fn read<R, V>(r: &mut R, tree: &mut tree::T<V>) -> Result<bounds::T, save::Error> where
+  R: io::Read,
+  V: save::Voxel + Clone,
+{
+  let header = save::read_header_with_magic(r, &MAGIC)?;
+  if header != save::Header::current::<V>() {
+    return Err(save::Error::Unsupported(header))
+  }
+
+  let bounds = read_bounds(r)?;
+  let out_of_bounds =
+    save::Error::Invalid(vec!(validate::Violation {
+      bounds: bounds,
+      problem: validate::Problem::OutOfBounds,
+    }));
+  if !validate::is_addressable(&bounds) {
+    return Err(out_of_bounds)
+  }
+
+  tree.grow_to_hold(&bounds);
+  let depth = tree.lg_size as i16 - bounds.lg_size;
+  if depth > validate::MAX_LG_SIZE as i16 {
+    return Err(out_of_bounds)
+  }
+
+  let node = tree.get_mut_or_create(&bounds);
+  *node = tree::Node::empty();
+  codec::read(r, slice::from_mut(node), (validate::MAX_LG_SIZE as i16 - depth) as u32)?;
+  Ok(bounds)
+}
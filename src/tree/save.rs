@@ -0,0 +1,282 @@
+//! A stable, versioned binary format for voxel trees.
+//!
+//! A save starts with a header of `MAGIC`, the format `VERSION` and the voxel type's id,
+//! followed by the tree's `lg_size` and its contents, encoded by `tree::codec`.
+//! Trees are validated when they're loaded.
+
+use std::io::{self, Read};
+
+use bounds;
+use tree;
+use tree::codec;
+use tree::validate;
+
+/// The bytes every save starts with.
+pub const MAGIC: [u8; 4] = *b"SVO\0";
+/// The version of the format written by `write`.
+///
+/// Version 1 wrote every node as a flags byte followed by its voxel and children;
+/// it can still be read.
+pub const VERSION: u32 = 2;
+
+/// Voxels that can be written to, and read from, a save.
+pub trait Voxel: Sized {
+  /// Identifies this voxel type in save headers.
+  /// This should change whenever the encoding of the voxel changes.
+  fn type_id() -> String;
+
+  /// Encode this voxel.
+  fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()>;
+
+  /// Decode a voxel encoded with `write`.
+  fn read<R: io::Read>(r: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_voxel_for_int(($t:ty, $bytes:expr) => {
+  impl Voxel for $t {
+    fn type_id() -> String {
+      stringify!($t).to_string()
+    }
+
+    fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+      w.write_all(&self.to_le_bytes())
+    }
+
+    fn read<R: io::Read>(r: &mut R) -> io::Result<Self> {
+      let mut bytes = [0; $bytes];
+      r.read_exact(&mut bytes)?;
+      Ok(<$t>::from_le_bytes(bytes))
+    }
+  }
+});
+
+impl_voxel_for_int!(u8, 1);
+impl_voxel_for_int!(i8, 1);
+impl_voxel_for_int!(u16, 2);
+impl_voxel_for_int!(i16, 2);
+impl_voxel_for_int!(u32, 4);
+impl_voxel_for_int!(i32, 4);
+impl_voxel_for_int!(u64, 8);
+impl_voxel_for_int!(i64, 8);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The header at the start of every save.
+pub struct Header {
+  /// The version of the format the rest of the save is in.
+  pub version: u32,
+  /// The `Voxel::type_id` of the saved voxels.
+  pub voxel_type: String,
+}
+
+impl Header {
+  /// The header that `write` would produce for a given voxel type.
+  pub fn current<V: Voxel>() -> Header {
+    Header {
+      version: VERSION,
+      voxel_type: V::type_id(),
+    }
+  }
+}
+
+#[derive(Debug)]
+/// Reasons a save can fail to load.
+pub enum Error {
+  #[allow(missing_docs)]
+  Io(io::Error),
+  /// The data doesn't start with `MAGIC`.
+  BadMagic([u8; 4]),
+  /// The save is from a version, or of a voxel type, that this loader can't handle.
+  Unsupported(Header),
+  /// The tree loaded, but isn't valid.
+  Invalid(Vec<validate::Violation>),
+}
+
+impl From<io::Error> for Error {
+  fn from(e: io::Error) -> Error {
+    Error::Io(e)
+  }
+}
+
+/// Write a `u32` in little-endian order.
+pub fn write_u32<W: io::Write>(w: &mut W, x: u32) -> io::Result<()> {
+  w.write_all(&x.to_le_bytes())
+}
+
+/// Read a little-endian `u32`.
+pub fn read_u32<R: io::Read>(r: &mut R) -> io::Result<u32> {
+  let mut bytes = [0; 4];
+  r.read_exact(&mut bytes)?;
+  Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read a single byte.
+pub fn read_u8<R: io::Read>(r: &mut R) -> io::Result<u8> {
+  let mut byte = [0];
+  r.read_exact(&mut byte)?;
+  Ok(byte[0])
+}
+
+/// Write a save header.
+pub fn write_header<W: io::Write>(w: &mut W, header: &Header) -> io::Result<()> {
+  write_header_with_magic(w, &MAGIC, header)
+}
+
+/// Write a header that starts with different magic bytes, for formats other than full saves.
+pub fn write_header_with_magic<W: io::Write>(w: &mut W, magic: &[u8; 4], header: &Header) -> io::Result<()> {
+  w.write_all(magic)?;
+  write_u32(w, header.version)?;
+  write_u32(w, header.voxel_type.len() as u32)?;
+  w.write_all(header.voxel_type.as_bytes())
+}
+
+/// Read a save header, checking the magic bytes.
+pub fn read_header<R: io::Read>(r: &mut R) -> Result<Header, Error> {
+  read_header_with_magic(r, &MAGIC)
+}
+
+/// Read a header written by `write_header_with_magic`.
+pub fn read_header_with_magic<R: io::Read>(r: &mut R, expected: &[u8; 4]) -> Result<Header, Error> {
+  let mut magic = [0; 4];
+  r.read_exact(&mut magic)?;
+  if magic != *expected {
+    return Err(Error::BadMagic(magic))
+  }
+
+  let version = read_u32(r)?;
+  let len = read_u32(r)?;
+  let mut voxel_type = String::new();
+  r.take(len as u64).read_to_string(&mut voxel_type)?;
+  if voxel_type.len() != len as usize {
+    return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated voxel type")))
+  }
+
+  Ok(Header {
+    version: version,
+    voxel_type: voxel_type,
+  })
+}
+
+const HAS_DATA: u8 = 1 << 0;
+const HAS_BRANCHES: u8 = 1 << 1;
+
+fn read_node_v1<R, V>(
+  r: &mut R,
+  tree_lg_size: u8,
+  bounds: &bounds::T,
+  node: &mut tree::Node<V>,
+) -> Result<(), Error> where
+  R: io::Read,
+  V: Voxel,
+{
+  // Refuse to recurse arbitrarily deep on bad input.
+  if tree_lg_size as i16 - bounds.lg_size > validate::MAX_LG_SIZE as i16 {
+    return Err(Error::Invalid(vec!(validate::Violation {
+      bounds: *bounds,
+      problem: validate::Problem::OutOfBounds,
+    })))
+  }
+
+  let flags = read_u8(r)?;
+  if flags & HAS_DATA != 0 {
+    node.data = Some(V::read(r)?);
+  }
+  if flags & HAS_BRANCHES != 0 {
+    for (x, plane) in node.force_branches().as_array_mut().iter_mut().enumerate() {
+    for (y, row) in plane.iter_mut().enumerate() {
+    for (z, child) in row.iter_mut().enumerate() {
+      read_node_v1(r, tree_lg_size, &tree::child_bounds(bounds, x, y, z), child)?;
+    }}}
+  }
+  Ok(())
+}
+
+/// Write a tree, including the header, in the current format.
+pub fn write<W, V>(w: &mut W, tree: &tree::T<V>) -> io::Result<()> where
+  W: io::Write,
+  V: Voxel + PartialEq,
+{
+  write_with(w, tree, codec::Compression::None)
+}
+
+/// Write a tree, including the header, in the current format with a given compression.
+pub fn write_with<W, V>(w: &mut W, tree: &tree::T<V>, compression: codec::Compression) -> io::Result<()> where
+  W: io::Write,
+  V: Voxel + PartialEq,
+{
+  write_header(w, &Header::current::<V>())?;
+  w.write_all(&[tree.lg_size])?;
+  codec::write(w, tree.contents.as_flat_array(), compression)
+}
+
+/// Read the body of a save in the current format, i.e. everything after the header.
+pub fn read_body<R, V>(r: &mut R) -> Result<tree::T<V>, Error> where
+  R: io::Read,
+  V: Voxel + Clone,
+{
+  let mut tree = tree::new();
+  tree.lg_size = read_u8(r)?;
+  if tree.lg_size > validate::MAX_LG_SIZE {
+    return Err(Error::Invalid(tree.validate()))
+  }
+
+  codec::read(r, tree.contents.as_flat_array_mut(), validate::MAX_LG_SIZE as u32)?;
+  Ok(tree)
+}
+
+fn read_body_v1<R, V>(r: &mut R) -> Result<tree::T<V>, Error> where
+  R: io::Read,
+  V: Voxel,
+{
+  let mut tree = tree::new();
+  tree.lg_size = read_u8(r)?;
+  if tree.lg_size > validate::MAX_LG_SIZE {
+    return Err(Error::Invalid(tree.validate()))
+  }
+
+  let lg_size = tree.lg_size;
+  for (x, plane) in tree.contents.as_array_mut().iter_mut().enumerate() {
+  for (y, row) in plane.iter_mut().enumerate() {
+  for (z, node) in row.iter_mut().enumerate() {
+    let bounds = bounds::new(x as i32 - 1, y as i32 - 1, z as i32 - 1, lg_size as i16);
+    read_node_v1(r, lg_size, &bounds, node)?;
+  }}}
+  Ok(tree)
+}
+
+/// Load a save in the current format, or one this module knows how to upgrade,
+/// rejecting anything else.
+pub fn read<R, V>(r: &mut R) -> Result<tree::T<V>, Error> where
+  R: io::Read,
+  V: Voxel + Clone,
+{
+  read_with(r, &mut |header, _| Err(Error::Unsupported(header.clone())))
+}
+
+/// Load a save, validating the resulting tree.
+/// Older versions of this format are upgraded automatically. Otherwise, if the header doesn't
+/// match `Header::current`, e.g. the save is from an older voxel encoding, `migrate` is called
+/// to read the rest of it.
+pub fn read_with<R, V, Migrate>(r: &mut R, migrate: &mut Migrate) -> Result<tree::T<V>, Error> where
+  R: io::Read,
+  V: Voxel + Clone,
+  Migrate: FnMut(&Header, &mut R) -> Result<tree::T<V>, Error>,
+{
+  let header = read_header(r)?;
+  let tree =
+    if header.voxel_type != V::type_id() {
+      migrate(&header, r)?
+    } else {
+      match header.version {
+        VERSION => read_body(r)?,
+        1 => read_body_v1(r)?,
+        _ => migrate(&header, r)?,
+      }
+    };
+
+  let violations = tree.validate();
+  if violations.is_empty() {
+    Ok(tree)
+  } else {
+    Err(Error::Invalid(violations))
+  }
+}
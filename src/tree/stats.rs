@@ -0,0 +1,68 @@
+//! Memory and shape statistics for voxel trees.
+
+use std::cmp::max;
+use std::collections::BTreeMap;
+use std::mem;
+
+use bounds;
+use tree;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A summary of the shape of a tree, as returned by `tree::T::stats`.
+pub struct T {
+  /// The number of nodes stored at each lg_size.
+  pub nodes_by_lg_size: BTreeMap<i16, usize>,
+  /// The number of nodes with a populated `data` slot.
+  pub populated: usize,
+  /// The number of `Inner::Branches` whose children are all empty leaves.
+  pub empty_branches: usize,
+  /// The depth of the deepest node. The top-level nodes have depth 1.
+  pub max_depth: u32,
+  /// Approximate heap usage of the tree, in bytes.
+  /// This counts the branch allocations, but not any heap data owned by the voxels themselves.
+  pub heap_bytes: usize,
+}
+
+#[allow(missing_docs)]
+pub fn empty() -> T {
+  T {
+    nodes_by_lg_size: BTreeMap::new(),
+    populated: 0,
+    empty_branches: 0,
+    max_depth: 0,
+    heap_bytes: 0,
+  }
+}
+
+impl T {
+  /// The total number of nodes in the tree.
+  pub fn nodes(&self) -> usize {
+    self.nodes_by_lg_size.values().sum()
+  }
+
+  /// Account for a single node at a given depth.
+  pub fn add<Voxel>(&mut self, depth: u32, bounds: &bounds::T, node: &tree::Node<Voxel>) {
+    *self.nodes_by_lg_size.entry(bounds.lg_size).or_insert(0) += 1;
+
+    if node.data.is_some() {
+      self.populated += 1;
+    }
+
+    if let tree::Inner::Branches(ref branches) = node.next {
+      self.heap_bytes += mem::size_of::<tree::Branches<Voxel>>();
+      let all_empty =
+        branches.as_flat_array().iter().all(|child| {
+          child.data.is_none() &&
+          match child.next {
+            tree::Inner::Empty => true,
+            tree::Inner::Branches(_) => false,
+          }
+        });
+      if all_empty {
+        self.empty_branches += 1;
+      }
+    }
+
+    self.max_depth = max(self.max_depth, depth);
+  }
+}
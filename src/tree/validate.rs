@@ -0,0 +1,104 @@
+//! Consistency checks for voxel trees, e.g. after loading one from an untrusted source.
+
+use bounds;
+use tree;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The kinds of problems that `tree::T::validate` looks for.
+pub enum Problem {
+  /// The tree is too large for its voxel coordinates to fit in an `i32`.
+  TooLarge,
+  /// The node is too deep below the top of the tree to be addressed.
+  OutOfBounds,
+  /// The node's voxel disagrees with the voxel of the parent with these bounds.
+  Inconsistent(bounds::T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A single problem found in a tree.
+pub struct Violation {
+  /// The bounds of the offending node.
+  pub bounds: bounds::T,
+  #[allow(missing_docs)]
+  pub problem: Problem,
+}
+
+/// The largest `lg_size` a tree can have while its bounds still fit in an `i32`.
+/// This is also the deepest a node can be below the top of the tree.
+pub const MAX_LG_SIZE: u8 = 30;
+
+/// Can a tree no larger than `MAX_LG_SIZE` hold `bounds`?
+pub fn is_addressable(bounds: &bounds::T) -> bool {
+  if bounds.lg_size > MAX_LG_SIZE as i16 || bounds.lg_size < -(MAX_LG_SIZE as i16) {
+    return false
+  }
+
+  let high = (1_i64 << MAX_LG_SIZE) >> bounds.lg_size.max(0) << (-bounds.lg_size).max(0);
+  let in_range = |x: i32| -high <= x as i64 && (x as i64) < high;
+  in_range(bounds.x) && in_range(bounds.y) && in_range(bounds.z)
+}
+
+/// Walk `tree`, reporting every violation to `report`.
+pub fn check<Voxel, Consistent, Report>(
+  tree: &tree::T<Voxel>,
+  consistent: &mut Consistent,
+  report: &mut Report,
+) where
+  Consistent: FnMut(&bounds::T, &Voxel, &bounds::T, &Voxel) -> bool,
+  Report: FnMut(Violation),
+{
+  if tree.lg_size > MAX_LG_SIZE {
+    report(Violation {
+      bounds: bounds::new(0, 0, 0, tree.lg_size as i16),
+      problem: Problem::TooLarge,
+    });
+    return
+  }
+
+  for (x, plane) in tree.contents.as_array().iter().enumerate() {
+  for (y, row) in plane.iter().enumerate() {
+  for (z, node) in row.iter().enumerate() {
+    check_node(tree, node, &tree.top_bounds(x, y, z), consistent, report);
+  }}}
+}
+
+fn check_node<Voxel, Consistent, Report>(
+  tree: &tree::T<Voxel>,
+  node: &tree::Node<Voxel>,
+  bounds: &bounds::T,
+  consistent: &mut Consistent,
+  report: &mut Report,
+) where
+  Consistent: FnMut(&bounds::T, &Voxel, &bounds::T, &Voxel) -> bool,
+  Report: FnMut(Violation),
+{
+  // Past this depth, child coordinates no longer fit in an `i32`,
+  // so there's no point in looking any further down.
+  if tree.lg_size as i16 - bounds.lg_size > MAX_LG_SIZE as i16 {
+    let empty = node.data.is_none() && matches!(node.next, tree::Inner::Empty);
+    if !empty {
+      report(Violation {
+        bounds: *bounds,
+        problem: Problem::OutOfBounds,
+      });
+    }
+    return
+  }
+
+  if let tree::Inner::Branches(ref branches) = node.next {
+    for (x, plane) in branches.as_array().iter().enumerate() {
+    for (y, row) in plane.iter().enumerate() {
+    for (z, child) in row.iter().enumerate() {
+      let child_bounds = tree::child_bounds(bounds, x, y, z);
+      if let (Some(parent), Some(voxel)) = (&node.data, &child.data) {
+        if !consistent(bounds, parent, &child_bounds, voxel) {
+          report(Violation {
+            bounds: child_bounds,
+            problem: Problem::Inconsistent(*bounds),
+          });
+        }
+      }
+      check_node(tree, child, &child_bounds, consistent, report);
+    }}}
+  }
+}